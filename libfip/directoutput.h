@@ -86,14 +86,21 @@ typedef struct SRequestStatus
 
 // HRESULT DirectOutput_Initialize(const wchar_t* wszPluginName);
 // Initialize the library
+// Waits (up to LIBFIP_SETTLE_TIMEOUT_MS environment variable milliseconds, 2000 by default)
+// for the devices already connected to get ready, so they are reported by the first DirectOutput_Enumerate
 // Parameters
 //    wszPluginName : null-terminated wchar_t name of the plugin. Used for debugging purposes. Can be NULL
 // Returns
 //    S_OK : succeeded
+//    E_FAIL : libusb could not be initialized (e.g. no access to USB)
 HRESULT extern DirectOutput_Initialize(const wchar_t* wszPluginName);
 
 // HRESULT DirectOutput_Deinitialize();
 // Cleanup the library
+// Blocks until callbacks running on other threads have returned; no callbacks are called afterwards.
+// Device threads and the USB event thread are stopped and joined before returning, so the library can be unloaded right after.
+// Safe to call from within a callback. When called from a device change callback, the threads are joined
+// only after that callback returns, so the library must not be unloaded from within it.
 // Parameters (None)
 // Returns
 //    S_OK : succeeded
@@ -101,6 +108,7 @@ HRESULT extern DirectOutput_Deinitialize();
 
 // HRESULT DirectOutput_RegisterDeviceCallback(Pfn_DirectOutput_DeviceChange pfnCb, void* pCtxt);
 // Register a callback. Callback will be called whenever a device is added or removed, or when DirectOutput_Enumerate is called
+// A device which had to be recovered from an internal failure is reported as removed and then added again
 // Parameters
 //     pfnCb : Pointer to the callback function to be called when a device is added or removed
 //     pCtxt : Caller supplied context pointer, passed to the callback function
@@ -110,6 +118,7 @@ HRESULT extern DirectOutput_RegisterDeviceCallback(Pfn_DirectOutput_DeviceChange
 
 // HRESULT DirectOutput_Enumerate();
 // Enumerate all devices currently attached. Calls DeviceChange callback.
+// Waits for the devices connected at the initialization to get ready (see DirectOutput_Initialize)
 // Parameters (None)
 // Returns
 //     S_OK : succeeded
@@ -364,6 +373,7 @@ HRESULT extern DirectOutput_SendServerFile(void* hDevice, DWORD dwServerId, DWOR
 //     S_OK : succeeded
 //     E_HANDLE : hDevice is not a valid device handle
 //     E_NOTIMPL : hDevice does not allow saving files
+//     E_DISKFULL : the device storage is full (libfip only, see DirectOutputEx_GetStorageUsage)
 //     E_FAIL : fatal error
 HRESULT extern DirectOutput_SaveFile(void* hDevice, DWORD dwPage, DWORD dwFile, DWORD cchFilename, const wchar_t* wszFilename, PSRequestStatus psStatus);
 
@@ -408,6 +418,138 @@ HRESULT extern DirectOutput_DeleteFile(void* hDevice, DWORD dwPage, DWORD dwFile
 //     E_FAIL : error
 HRESULT extern DirectOutput_GetSerialNumber(void* hDevice, wchar_t* pszSerialNumber, DWORD dwSize);
 
+//=============================================================================
+// libfip extensions: device information
+
+#define DEVICE_STRING_MANUFACTURER 0
+#define DEVICE_STRING_PRODUCT 1
+#define DEVICE_STRING_SERIAL_NUMBER 2
+
+// HRESULT DirectOutputEx_GetDeviceString(void* hDevice, DWORD dwStringKind, wchar_t* pszString, DWORD dwSize);
+// Get one of the device USB descriptor strings (en-US if the device provides it, otherwise the first language that works)
+// Parameters
+//     hDevice : opaque device handle
+//     dwStringKind : one of the DEVICE_STRING_* values
+//     pszString : the null-terminated string
+//     dwSize : the size of pszString, in characters
+// Returns
+//     S_OK : succeeded
+//     E_HANDLE : hDevice is not a valid device handle
+//     E_INVALIDARG : an argument is invalid
+//     E_NOTIMPL : the device doesn't provide this string
+//     E_BUFFERTOOSMALL : pszString is too small
+HRESULT extern DirectOutputEx_GetDeviceString(void* hDevice, DWORD dwStringKind, wchar_t* pszString, DWORD dwSize);
+
+//=============================================================================
+// libfip extensions: idle behavior
+
+#define IDLE_ACTION_NONE 0
+#define IDLE_ACTION_BLANK 1
+#define IDLE_ACTION_CYCLE_FILES 2
+#define IDLE_ACTION_CYCLE_IMAGES 3
+
+// HRESULT DirectOutputEx_SetIdleBehavior(void* hDevice, DWORD dwPage, DWORD dwAction, DWORD dwTimeoutMs, DWORD dwIntervalMs, DWORD dwIndex, DWORD cFiles, const DWORD* pdwFiles);
+// Set what the device shows after dwTimeoutMs without any command (burn-in prevention).
+// The next command wakes the device up and restores the last image of the page
+// Parameters
+//     hDevice : opaque device handle
+//     dwPage : page to show the idle content on
+//     dwAction : one of the IDLE_ACTION_* values; IDLE_ACTION_NONE disables the idle behavior
+//     dwTimeoutMs : time without commands before the device is considered idle
+//     dwIntervalMs : time between cycling steps
+//     dwIndex : image index to display files at (IDLE_ACTION_CYCLE_FILES only)
+//     cFiles : number of files in pdwFiles (IDLE_ACTION_CYCLE_FILES only)
+//     pdwFiles : ids of saved files to cycle through (IDLE_ACTION_CYCLE_FILES only)
+// Returns
+//     S_OK : succeeded
+//     E_HANDLE : hDevice is not a valid device handle
+//     E_INVALIDARG : an argument is invalid
+HRESULT extern DirectOutputEx_SetIdleBehavior(void* hDevice, DWORD dwPage, DWORD dwAction, DWORD dwTimeoutMs, DWORD dwIntervalMs, DWORD dwIndex, DWORD cFiles, const DWORD* pdwFiles);
+
+//=============================================================================
+// libfip extensions: device storage
+
+#ifndef E_DISKFULL
+#define E_DISKFULL 0x80070070
+#endif
+
+// HRESULT DirectOutputEx_GetStorageUsage(void* hDevice, uint64_t* pqwUsed, uint64_t* pqwRemaining);
+// Get the device flash storage usage. The device doesn't report its capacity,
+// so it's estimated once the device rejects a file for being out of space (DirectOutput_SaveFile returns E_DISKFULL)
+// Parameters
+//     hDevice : opaque device handle
+//     pqwUsed : bytes taken by the files saved (and not deleted) since the device has been connected
+//     pqwRemaining : estimated bytes remaining, UINT64_MAX if not known yet
+// Returns
+//     S_OK : succeeded
+//     E_HANDLE : hDevice is not a valid device handle
+//     E_INVALIDARG : an argument is invalid
+HRESULT extern DirectOutputEx_GetStorageUsage(void* hDevice, uint64_t* pqwUsed, uint64_t* pqwRemaining);
+
+//=============================================================================
+// libfip extensions: latency measurement
+
+typedef struct _FrameStats
+{
+	uint64_t frames;
+	uint64_t failedFrames;
+	uint64_t lastLatencyUs;
+	uint64_t minLatencyUs;
+	uint64_t maxLatencyUs;
+	uint64_t avgLatencyUs;
+} FrameStats, *PFrameStats;
+
+// HRESULT DirectOutputEx_SetLatencyMode(void* hDevice, DWORD dwEnabled);
+// Enable (1) or disable (0) the latency measurement mode. While enabled, a frame counter is drawn in the corner
+// of every image sent with DirectOutput_SetImage (16 black/white 4x4 cells, most significant bit first),
+// and the time from the call to the device acknowledging the image is collected. Enabling resets the statistics
+// Returns
+//     S_OK : succeeded
+//     E_HANDLE : hDevice is not a valid device handle
+//     E_INVALIDARG : an argument is invalid
+HRESULT extern DirectOutputEx_SetLatencyMode(void* hDevice, DWORD dwEnabled);
+
+// HRESULT DirectOutputEx_GetFrameStats(void* hDevice, PFrameStats psStats);
+// Get the statistics collected in the latency measurement mode
+// Returns
+//     S_OK : succeeded
+//     E_HANDLE : hDevice is not a valid device handle
+//     E_INVALIDARG : an argument is invalid
+HRESULT extern DirectOutputEx_GetFrameStats(void* hDevice, PFrameStats psStats);
+
+//=============================================================================
+// libfip extensions: device groups
+
+// HRESULT DirectOutputEx_SetGroup(const wchar_t* wszGroupName, DWORD dwSerialNumbers, const wchar_t** pwszSerialNumbers);
+// Define (or redefine) a named group of devices
+// Parameters
+//     wszGroupName : null-terminated wchar_t name of the group
+//     dwSerialNumbers : number of serial numbers in pwszSerialNumbers
+//     pwszSerialNumbers : null-terminated wchar_t serial numbers of the devices in the group
+// Returns
+//     S_OK : succeeded
+//     E_INVALIDARG : an argument is invalid
+HRESULT extern DirectOutputEx_SetGroup(const wchar_t* wszGroupName, DWORD dwSerialNumbers, const wchar_t** pwszSerialNumbers);
+
+// HRESULT DirectOutputEx_RemoveGroup(const wchar_t* wszGroupName);
+// Remove a named group of devices
+// Returns
+//     S_OK : succeeded
+//     E_HANDLE : there is no such group
+HRESULT extern DirectOutputEx_RemoveGroup(const wchar_t* wszGroupName);
+
+// HRESULT DirectOutputEx_Group*(const wchar_t* wszGroupName, ...);
+// Perform an operation on all connected devices of the group. Devices of the group that are not connected are skipped
+// Returns
+//     S_OK : succeeded on every connected device of the group
+//     E_HANDLE : there is no such group
+//     E_INVALIDARG : an argument is invalid
+//     E_FAIL : the operation has failed on some of the devices
+HRESULT extern DirectOutputEx_GroupClearImage(const wchar_t* wszGroupName, DWORD dwPage);
+HRESULT extern DirectOutputEx_GroupSetImage(const wchar_t* wszGroupName, DWORD dwPage, DWORD cbValue, const void* pvValue);
+HRESULT extern DirectOutputEx_GroupDisplayFile(const wchar_t* wszGroupName, DWORD dwPage, DWORD dwIndex, DWORD dwFile);
+HRESULT extern DirectOutputEx_GroupSetLeds(const wchar_t* wszGroupName, DWORD dwPage, DWORD dwValue);
+
 //=============================================================================
 // Function Pointers
 
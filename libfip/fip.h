@@ -0,0 +1,148 @@
+/*
+ * libfip native API
+ *
+ * A small alternative to the DirectOutput-compatible API (see directoutput.h)
+ * for using the panels directly. Uses the C calling convention on all platforms.
+ */
+#pragma once
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define FIP_API_VERSION 1
+
+//=============================================================================
+// Return codes
+
+#define FIP_OK 0
+#define FIP_E_INVALID_ARG -1
+#define FIP_E_NO_DEVICE -2
+#define FIP_E_DEVICE -3
+
+//=============================================================================
+// Buttons
+
+#define FIP_BUTTON_S1 0x0100
+#define FIP_BUTTON_S2 0x0200
+#define FIP_BUTTON_S3 0x0400
+#define FIP_BUTTON_S4 0x0800
+#define FIP_BUTTON_S5 0x1000
+#define FIP_BUTTON_S6 0x2000
+#define FIP_BUTTON_LEFT_ANTICLOCKWISE 0x4000
+#define FIP_BUTTON_LEFT_CLOCKWISE 0x8000
+#define FIP_BUTTON_UP 0x0001
+#define FIP_BUTTON_DOWN 0x0002
+#define FIP_BUTTON_RIGHT_ANTICLOCKWISE 0x0004
+#define FIP_BUTTON_RIGHT_CLOCKWISE 0x0008
+
+#define FIP_FRAME_SIZE 0x38400  // 320x240, 24 bits per pixel
+
+typedef struct fip_device fip_device;
+
+typedef void (*fip_buttons_callback)(fip_device* device, uint32_t buttons, void* ctx);
+
+typedef struct fip_device_info {
+    char serial_number[64];  // null-terminated UTF-8, empty if unknown
+    char manufacturer[64];  // null-terminated UTF-8, empty if unknown
+    char product[64];  // null-terminated UTF-8, empty if unknown
+    uint8_t bus_number;
+    uint8_t address;
+} fip_device_info;
+
+// uint32_t fip_api_version(void);
+// Returns
+//     FIP_API_VERSION of the library; the functions and structs of this header only change with it
+uint32_t fip_api_version(void);
+
+// fip_device* fip_open(const char* serial_number, uint32_t timeout_ms);
+// Open a device, initializing the library if needed.
+// A device may be opened more than once; each handle gets its own buttons callback and queue
+// Parameters
+//     serial_number : null-terminated UTF-8 serial number of the device to open. NULL opens the first device found
+//     timeout_ms : how long to wait for the device to show up
+// Returns
+//     the device, or NULL if it's not found or the library can't be initialized (e.g. no access to USB)
+fip_device* fip_open(const char* serial_number, uint32_t timeout_ms);
+
+// int32_t fip_set_frame(fip_device* device, uint8_t page, const uint8_t* frame, size_t frame_size);
+// Show a frame on the device
+// Parameters
+//     device : device returned by fip_open
+//     page : page to show the frame on
+//     frame : FIP_FRAME_SIZE bytes of pixel data
+//     frame_size : must be FIP_FRAME_SIZE
+// Returns
+//     FIP_OK : succeeded
+//     FIP_E_INVALID_ARG : an argument is invalid
+//     FIP_E_NO_DEVICE : the device is gone
+//     FIP_E_DEVICE : the device has failed to show the frame
+int32_t fip_set_frame(fip_device* device, uint8_t page, const uint8_t* frame, size_t frame_size);
+
+// int32_t fip_clear_frame(fip_device* device, uint8_t page);
+// Clear the frame shown on the device
+// Returns
+//     FIP_OK : succeeded
+//     FIP_E_INVALID_ARG : an argument is invalid
+//     FIP_E_NO_DEVICE : the device is gone
+//     FIP_E_DEVICE : the device has failed to clear the frame
+int32_t fip_clear_frame(fip_device* device, uint8_t page);
+
+// int32_t fip_set_led(fip_device* device, uint8_t page, uint8_t index, uint8_t value);
+// Turn a LED of the device on (value != 0) or off (value == 0)
+// Returns
+//     FIP_OK : succeeded
+//     FIP_E_INVALID_ARG : an argument is invalid (including a LED index the device doesn't have)
+//     FIP_E_NO_DEVICE : the device is gone
+//     FIP_E_DEVICE : the device has failed to set the LED
+int32_t fip_set_led(fip_device* device, uint8_t page, uint8_t index, uint8_t value);
+
+// int32_t fip_get_info(fip_device* device, fip_device_info* info);
+// Get information about the device
+// Returns
+//     FIP_OK : succeeded
+//     FIP_E_INVALID_ARG : an argument is invalid
+//     FIP_E_NO_DEVICE : the device is gone
+int32_t fip_get_info(fip_device* device, fip_device_info* info);
+
+// int32_t fip_on_buttons(fip_device* device, fip_buttons_callback callback, void* ctx);
+// Set a callback to be called (from a library thread) whenever the state of the buttons changes
+// Parameters
+//     device : device returned by fip_open
+//     callback : the callback, called with a combination of FIP_BUTTON_* values. NULL removes the callback
+//     ctx : caller supplied context pointer, passed to the callback
+// Returns
+//     FIP_OK : succeeded
+//     FIP_E_INVALID_ARG : an argument is invalid
+int32_t fip_on_buttons(fip_device* device, fip_buttons_callback callback, void* ctx);
+
+// int32_t fip_get_buttons(fip_device* device, uint32_t* buttons);
+// Get the current state of the buttons (a combination of FIP_BUTTON_* values)
+// Returns
+//     FIP_OK : succeeded
+//     FIP_E_INVALID_ARG : an argument is invalid
+int32_t fip_get_buttons(fip_device* device, uint32_t* buttons);
+
+// int32_t fip_poll_buttons(fip_device* device, uint32_t* buttons);
+// Get the next change of the buttons state, for use without a callback.
+// Up to 64 changes are kept; older ones are dropped if not polled in time
+// Returns
+//     1 : *buttons is set to the buttons state after the change
+//     0 : there were no changes since the last call
+//     FIP_E_INVALID_ARG : an argument is invalid
+int32_t fip_poll_buttons(fip_device* device, uint32_t* buttons);
+
+// void fip_close(fip_device* device);
+// Close a device. The library is deinitialized with the last device closed
+// (unless it's also used via DirectOutput_Initialize)
+// Blocks until the device callback, if running on another thread, has returned; no callbacks are called afterwards
+// Parameters
+//     device : device returned by fip_open. Must not be used afterwards
+void fip_close(fip_device* device);
+
+#ifdef __cplusplus
+};
+#endif
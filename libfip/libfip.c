@@ -125,3 +125,36 @@ HRESULT WINAPI ProxyDirectOutput_DeleteFile(void* hDevice, DWORD dwPage, DWORD d
 HRESULT WINAPI ProxyDirectOutput_GetSerialNumber(void* hDevice, LPWSTR pszSerialNumber, DWORD dwSize) {
     return DirectOutput_GetSerialNumber(hDevice, pszSerialNumber, dwSize);
 }
+HRESULT WINAPI ProxyDirectOutputEx_SetGroup(LPCWSTR wszGroupName, DWORD dwSerialNumbers, const LPCWSTR* pwszSerialNumbers) {
+    return DirectOutputEx_SetGroup(wszGroupName, dwSerialNumbers, pwszSerialNumbers);
+}
+HRESULT WINAPI ProxyDirectOutputEx_RemoveGroup(LPCWSTR wszGroupName) {
+    return DirectOutputEx_RemoveGroup(wszGroupName);
+}
+HRESULT WINAPI ProxyDirectOutputEx_GroupClearImage(LPCWSTR wszGroupName, DWORD dwPage) {
+    return DirectOutputEx_GroupClearImage(wszGroupName, dwPage);
+}
+HRESULT WINAPI ProxyDirectOutputEx_GroupSetImage(LPCWSTR wszGroupName, DWORD dwPage, DWORD cbValue, const void* pvValue) {
+    return DirectOutputEx_GroupSetImage(wszGroupName, dwPage, cbValue, pvValue);
+}
+HRESULT WINAPI ProxyDirectOutputEx_GroupDisplayFile(LPCWSTR wszGroupName, DWORD dwPage, DWORD dwIndex, DWORD dwFile) {
+    return DirectOutputEx_GroupDisplayFile(wszGroupName, dwPage, dwIndex, dwFile);
+}
+HRESULT WINAPI ProxyDirectOutputEx_GroupSetLeds(LPCWSTR wszGroupName, DWORD dwPage, DWORD dwValue) {
+    return DirectOutputEx_GroupSetLeds(wszGroupName, dwPage, dwValue);
+}
+HRESULT WINAPI ProxyDirectOutputEx_GetDeviceString(void* hDevice, DWORD dwStringKind, LPWSTR pszString, DWORD dwSize) {
+    return DirectOutputEx_GetDeviceString(hDevice, dwStringKind, pszString, dwSize);
+}
+HRESULT WINAPI ProxyDirectOutputEx_SetIdleBehavior(void* hDevice, DWORD dwPage, DWORD dwAction, DWORD dwTimeoutMs, DWORD dwIntervalMs, DWORD dwIndex, DWORD cFiles, const DWORD* pdwFiles) {
+    return DirectOutputEx_SetIdleBehavior(hDevice, dwPage, dwAction, dwTimeoutMs, dwIntervalMs, dwIndex, cFiles, pdwFiles);
+}
+HRESULT WINAPI ProxyDirectOutputEx_SetLatencyMode(void* hDevice, DWORD dwEnabled) {
+    return DirectOutputEx_SetLatencyMode(hDevice, dwEnabled);
+}
+HRESULT WINAPI ProxyDirectOutputEx_GetFrameStats(void* hDevice, PFrameStats psStats) {
+    return DirectOutputEx_GetFrameStats(hDevice, psStats);
+}
+HRESULT WINAPI ProxyDirectOutputEx_GetStorageUsage(void* hDevice, uint64_t* pqwUsed, uint64_t* pqwRemaining) {
+    return DirectOutputEx_GetStorageUsage(hDevice, pqwUsed, pqwRemaining);
+}
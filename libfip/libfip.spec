@@ -21,3 +21,14 @@
 @ stdcall -ret64 DirectOutput_DisplayFile (ptr long long long ptr) ProxyDirectOutput_DisplayFile
 @ stdcall -ret64 DirectOutput_DeleteFile (ptr long long ptr) ProxyDirectOutput_DeleteFile
 @ stdcall -ret64 DirectOutput_GetSerialNumber (ptr ptr long) ProxyDirectOutput_GetSerialNumber
+@ stdcall -ret64 DirectOutputEx_SetGroup (wstr long ptr) ProxyDirectOutputEx_SetGroup
+@ stdcall -ret64 DirectOutputEx_RemoveGroup (wstr) ProxyDirectOutputEx_RemoveGroup
+@ stdcall -ret64 DirectOutputEx_GroupClearImage (wstr long) ProxyDirectOutputEx_GroupClearImage
+@ stdcall -ret64 DirectOutputEx_GroupSetImage (wstr long long ptr) ProxyDirectOutputEx_GroupSetImage
+@ stdcall -ret64 DirectOutputEx_GroupDisplayFile (wstr long long long) ProxyDirectOutputEx_GroupDisplayFile
+@ stdcall -ret64 DirectOutputEx_GroupSetLeds (wstr long long) ProxyDirectOutputEx_GroupSetLeds
+@ stdcall -ret64 DirectOutputEx_GetDeviceString (ptr long ptr long) ProxyDirectOutputEx_GetDeviceString
+@ stdcall -ret64 DirectOutputEx_SetIdleBehavior (ptr long long long long long long ptr) ProxyDirectOutputEx_SetIdleBehavior
+@ stdcall -ret64 DirectOutputEx_SetLatencyMode (ptr long) ProxyDirectOutputEx_SetLatencyMode
+@ stdcall -ret64 DirectOutputEx_GetFrameStats (ptr ptr) ProxyDirectOutputEx_GetFrameStats
+@ stdcall -ret64 DirectOutputEx_GetStorageUsage (ptr ptr ptr) ProxyDirectOutputEx_GetStorageUsage
@@ -0,0 +1,75 @@
+// One-shot broadcast operations on a set of displays, for multi-panel rigs at startup/shutdown
+
+use std::{fs, thread::sleep, time::Duration};
+
+use libfip::devices::{self, Broadcast};
+
+const SETTLE_TIMEOUT: Duration = Duration::from_secs(2);
+const GROUP_NAME: &str = "fipctl";
+
+fn init() -> Result<devices::State, String> {
+    let state = devices::init(SETTLE_TIMEOUT).map_err(|_| "Cannot initialize libusb".to_owned())?;
+    while !state.settled() {
+        sleep(Duration::from_millis(20));
+    }
+    Ok(state)
+}
+
+pub fn list() -> Result<(), String> {
+    let state = init()?;
+    for addr in state.display_addrs() {
+        let Some(display) = state.display_by_addr(&addr) else { continue };
+        let Some(serial_number) = display.serial_number() else { continue };
+        println!("{}-{}: {}", addr.0, addr.1, serial_number);
+    }
+    Ok(())
+}
+
+fn parse_u8(value: &str, what: &str) -> Result<u8, String> {
+    value
+        .parse()
+        .map_err(|_| format!("Invalid {} {:?}", what, value))
+}
+
+// `serial_numbers` is comma-separated; `operation` is one of
+// `clear <page>`, `image <page> <raw frame file>`, `file <page> <index> <file>`, `leds <page> on|off`
+pub fn broadcast(serial_numbers: &str, operation: &[&str]) -> Result<(), String> {
+    let data;
+    let operation = match operation {
+        ["clear", page] => Broadcast::ClearImage { page: parse_u8(page, "page")? },
+        ["image", page, path] => {
+            data = fs::read(path).map_err(|err| format!("Cannot read {:?} ({})", path, err))?;
+            Broadcast::SetImageData {
+                page: parse_u8(page, "page")?,
+                data: data
+                    .as_slice()
+                    .try_into()
+                    .map_err(|_| format!("{:?} is not a raw 320x240 BGR frame", path))?,
+            }
+        }
+        ["file", page, index, file] => Broadcast::DisplayFile {
+            page: parse_u8(page, "page")?,
+            index: parse_u8(index, "index")?,
+            file: parse_u8(file, "file")?,
+        },
+        ["leds", page, value @ ("on" | "off")] => Broadcast::SetLeds {
+            page: parse_u8(page, "page")?,
+            value: *value == "on",
+        },
+        _ => return Err(format!("Unknown group operation {:?}", operation.join(" "))),
+    };
+
+    let state = init()?;
+    state.set_group(
+        GROUP_NAME.to_owned(),
+        serial_numbers.split(',').map(str::to_owned).collect(),
+    );
+    let displays = state.group_displays(GROUP_NAME).unwrap_or_default();
+    match devices::broadcast(&displays, &operation) {
+        0 => {
+            println!("Done on {} display(s)", displays.len());
+            Ok(())
+        }
+        failed => Err(format!("Failed on {} of {} display(s)", failed, displays.len())),
+    }
+}
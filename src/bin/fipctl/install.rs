@@ -0,0 +1,161 @@
+// Locating the Saitek/Logitech DirectOutput installation and swapping its DLL for ours
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use winreg::{
+    enums::{HKEY_LOCAL_MACHINE, KEY_READ, KEY_WOW64_32KEY, KEY_WOW64_64KEY},
+    RegKey,
+};
+
+pub const OUR_DLL_NAME: &str = "libfip.dll";
+const DLL_NAME: &str = "DirectOutput.dll";
+const BACKUP_SUFFIX: &str = ".orig";
+// marks an installation there was no original DLL to back up for
+const NO_BACKUP_SUFFIX: &str = ".noorig";
+
+const IMAGE_FILE_MACHINE_I386: u16 = 0x014c;
+const IMAGE_FILE_MACHINE_AMD64: u16 = 0x8664;
+
+// (key, value) pairs the vendor installers put the DLL location into
+const REGISTRY_LOCATIONS: [(&str, &str); 2] = [
+    (r"SOFTWARE\Logitech\DirectOutput", "DirectOutput"),
+    (r"SOFTWARE\Saitek\DirectOutput", "DirectOutput"),
+];
+
+// Both 32- and 64-bit installations may be present, each in its own registry view
+const ALL_VIEWS: [u32; 2] = [KEY_WOW64_64KEY, KEY_WOW64_32KEY];
+
+fn installed_dll_paths(views: &[u32]) -> Vec<PathBuf> {
+    let hklm = RegKey::predef(HKEY_LOCAL_MACHINE);
+    let mut paths = Vec::new();
+    for (key_path, value_name) in REGISTRY_LOCATIONS {
+        for &view in views {
+            let Ok(key) = hklm.open_subkey_with_flags(key_path, KEY_READ | view) else { continue };
+            let Ok(value) = key.get_value::<String, _>(value_name) else { continue };
+            let mut path = PathBuf::from(value.trim_end_matches('\0'));
+            if path.is_dir() {
+                path.push(DLL_NAME);
+            }
+            log::debug!("Found {:?} in {}\\{}", path, key_path, value_name);
+            if !paths.contains(&path) {
+                paths.push(path);
+            }
+        }
+    }
+    paths
+}
+
+fn suffixed_path(dll_path: &Path, suffix: &str) -> PathBuf {
+    let mut path = dll_path.as_os_str().to_owned();
+    path.push(suffix);
+    PathBuf::from(path)
+}
+
+fn backup_path(dll_path: &Path) -> PathBuf {
+    suffixed_path(dll_path, BACKUP_SUFFIX)
+}
+
+fn no_backup_marker_path(dll_path: &Path) -> PathBuf {
+    suffixed_path(dll_path, NO_BACKUP_SUFFIX)
+}
+
+fn installed_dll_paths_or_err(views: &[u32]) -> Result<Vec<PathBuf>, String> {
+    let paths = installed_dll_paths(views);
+    match paths.is_empty() {
+        true => Err("Cannot find DirectOutput installation in the registry".to_owned()),
+        false => Ok(paths),
+    }
+}
+
+// The registry view of the installations the DLL can be loaded by, from its PE header
+fn dll_view(dll_path: &Path) -> Result<u32, String> {
+    let data = fs::read(dll_path).map_err(|err| format!("Cannot read {:?} ({})", dll_path, err))?;
+    let bytes_at = |offset: usize, len: usize| data.get(offset..offset + len);
+    let machine = bytes_at(0x3c, 4)
+        .map(|pe_offset| u32::from_le_bytes(pe_offset.try_into().unwrap()) as usize)
+        .filter(|&pe_offset| bytes_at(pe_offset, 4) == Some(b"PE\0\0".as_slice()))
+        .and_then(|pe_offset| bytes_at(pe_offset + 4, 2))
+        .map(|machine| u16::from_le_bytes(machine.try_into().unwrap()))
+        .ok_or_else(|| format!("{:?} is not a DLL", dll_path))?;
+    match machine {
+        IMAGE_FILE_MACHINE_AMD64 => Ok(KEY_WOW64_64KEY),
+        IMAGE_FILE_MACHINE_I386 => Ok(KEY_WOW64_32KEY),
+        _ => Err(format!("{:?} is built for an unsupported architecture ({:#06x})", dll_path, machine)),
+    }
+}
+
+pub fn install(our_dll_path: &Path) -> Result<(), String> {
+    if !our_dll_path.is_file() {
+        return Err(format!("{:?} doesn't exist", our_dll_path));
+    }
+    // a 64-bit DLL would break 32-bit apps using the 32-bit installation, and the other way around
+    let view = dll_view(our_dll_path)?;
+    for dll_path in installed_dll_paths_or_err(&[view])? {
+        let backup_path = backup_path(&dll_path);
+        let no_backup_marker_path = no_backup_marker_path(&dll_path);
+        // never overwrite the backup, it may be the only copy of the original DLL left
+        if !backup_path.exists() && !no_backup_marker_path.exists() {
+            match dll_path.exists() {
+                true => {
+                    fs::copy(&dll_path, &backup_path)
+                        .map_err(|err| format!("Cannot back up {:?} ({})", dll_path, err))?;
+                    println!("Backed up {:?} to {:?}", dll_path, backup_path);
+                }
+                false => {
+                    fs::write(&no_backup_marker_path, b"")
+                        .map_err(|err| format!("Cannot create {:?} ({})", no_backup_marker_path, err))?;
+                }
+            }
+        }
+        fs::copy(our_dll_path, &dll_path)
+            .map_err(|err| format!("Cannot install {:?} ({})", dll_path, err))?;
+        println!("Installed {:?}", dll_path);
+    }
+    Ok(())
+}
+
+pub fn revert() -> Result<(), String> {
+    for dll_path in installed_dll_paths_or_err(&ALL_VIEWS)? {
+        let backup_path = backup_path(&dll_path);
+        let no_backup_marker_path = no_backup_marker_path(&dll_path);
+        if no_backup_marker_path.exists() {
+            // there was no original DLL, so there's nothing to restore: just remove ours
+            if dll_path.exists() {
+                fs::remove_file(&dll_path)
+                    .map_err(|err| format!("Cannot remove {:?} ({})", dll_path, err))?;
+            }
+            fs::remove_file(&no_backup_marker_path)
+                .map_err(|err| format!("Cannot remove {:?} ({})", no_backup_marker_path, err))?;
+            println!("Removed {:?}", dll_path);
+            continue;
+        }
+        if !backup_path.exists() {
+            println!("No backup of {:?} found, skipping it", dll_path);
+            continue;
+        }
+        fs::copy(&backup_path, &dll_path)
+            .map_err(|err| format!("Cannot restore {:?} ({})", dll_path, err))?;
+        fs::remove_file(&backup_path)
+            .map_err(|err| format!("Cannot remove {:?} ({})", backup_path, err))?;
+        println!("Restored {:?}", dll_path);
+    }
+    Ok(())
+}
+
+pub fn status() -> Result<(), String> {
+    for dll_path in installed_dll_paths_or_err(&ALL_VIEWS)? {
+        println!(
+            "{:?}: {}",
+            dll_path,
+            match (backup_path(&dll_path).exists(), no_backup_marker_path(&dll_path).exists()) {
+                (true, _) => "replaced (original backed up)",
+                (false, true) => "installed (there was no original)",
+                (false, false) => "original",
+            }
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,85 @@
+mod group;
+#[cfg(windows)]
+mod install;
+
+use std::{env, path::PathBuf, process::ExitCode};
+
+const USAGE: &str = "\
+Usage: fipctl <command>
+
+Commands:
+    install [--dll <path>]  replace the installed DirectOutput.dll with this implementation
+                            (by default, libfip.dll next to fipctl), backing the original up;
+                            only the installation of the same architecture (32/64-bit) as the DLL
+    revert                  restore the original DirectOutput.dll from the backup
+    status                  show where DirectOutput.dll is installed and whether it's replaced
+    list                    list connected displays (bus-address: serial number)
+    group <serial>[,<serial>...] <operation>
+                            perform an operation on all the listed displays that are connected:
+        clear <page>                clear the page image
+        image <page> <path>         show a raw 320x240 BGR frame (230400 bytes)
+        file <page> <index> <file>  show a file saved on the devices
+        leds <page> on|off          turn all the LEDs on or off";
+
+fn main() -> ExitCode {
+    pretty_env_logger::init();
+
+    let args: Vec<String> = env::args().skip(1).collect();
+    let result = match args.iter().map(String::as_str).collect::<Vec<_>>().as_slice() {
+        ["install"] => install(None),
+        ["install", "--dll", path] => install(Some(PathBuf::from(*path))),
+        ["revert"] => revert(),
+        ["status"] => status(),
+        ["list"] => group::list(),
+        ["group", serial_numbers, operation @ ..] if !operation.is_empty() => {
+            group::broadcast(serial_numbers, operation)
+        }
+        _ => {
+            eprintln!("{}", USAGE);
+            return ExitCode::FAILURE;
+        }
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("{}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(windows)]
+fn install(dll_path: Option<PathBuf>) -> Result<(), String> {
+    let dll_path = match dll_path {
+        Some(dll_path) => dll_path,
+        None => env::current_exe()
+            .map_err(|err| format!("Cannot locate fipctl itself ({})", err))?
+            .with_file_name(install::OUR_DLL_NAME),
+    };
+    install::install(&dll_path)
+}
+
+#[cfg(windows)]
+fn revert() -> Result<(), String> {
+    install::revert()
+}
+
+#[cfg(windows)]
+fn status() -> Result<(), String> {
+    install::status()
+}
+
+#[cfg(not(windows))]
+fn install(_dll_path: Option<PathBuf>) -> Result<(), String> {
+    Err("Installation is only supported on Windows".to_owned())
+}
+
+#[cfg(not(windows))]
+fn revert() -> Result<(), String> {
+    Err("Installation is only supported on Windows".to_owned())
+}
+
+#[cfg(not(windows))]
+fn status() -> Result<(), String> {
+    Err("Installation is only supported on Windows".to_owned())
+}
@@ -0,0 +1,68 @@
+use std::{
+    sync::{Arc, Condvar, Mutex},
+    thread::{self, ThreadId},
+};
+
+// Coordinates calls into application-provided callbacks with library teardown:
+// once closed, no new callbacks are let through, and `close` blocks until the
+// ones already running have returned (except those on the calling thread,
+// so calling `DirectOutput_Deinitialize` from within a callback doesn't deadlock).
+#[derive(Default)]
+pub struct CallbackGate {
+    state: Mutex<CallbackGateState>,
+    idle: Condvar,
+}
+
+#[derive(Default)]
+struct CallbackGateState {
+    closed: bool,
+    in_flight: Vec<ThreadId>,
+}
+
+// Owns the gate, as the callback may drop the last other reference to it
+// (e.g. the hotplug handler it's called from, on `DirectOutput_Deinitialize`)
+pub struct CallbackGuard {
+    gate: Arc<CallbackGate>,
+}
+
+impl CallbackGate {
+    pub fn enter(self: &Arc<Self>) -> Option<CallbackGuard> {
+        let mut state = self.state.lock().expect("Callback gate is poisoned");
+        if state.closed {
+            return None;
+        }
+        state.in_flight.push(thread::current().id());
+        Some(CallbackGuard { gate: self.clone() })
+    }
+
+    pub fn close(&self) {
+        let current_thread_id = thread::current().id();
+        let mut state = self.state.lock().expect("Callback gate is poisoned");
+        state.closed = true;
+        if state.in_flight.contains(&current_thread_id) {
+            log::debug!("Callback gate is being closed from within a callback");
+        }
+        while state.in_flight.iter().any(|id| *id != current_thread_id) {
+            log::trace!(
+                "Waiting for {} in-flight callback(s) to finish",
+                state.in_flight.len()
+            );
+            state = self.idle.wait(state).expect("Callback gate is poisoned");
+        }
+    }
+}
+
+impl Drop for CallbackGuard {
+    fn drop(&mut self) {
+        let current_thread_id = thread::current().id();
+        let mut state = self.gate.state.lock().expect("Callback gate is poisoned");
+        if let Some(pos) = state
+            .in_flight
+            .iter()
+            .rposition(|id| *id == current_thread_id)
+        {
+            state.in_flight.swap_remove(pos);
+        }
+        self.gate.idle.notify_all();
+    }
+}
@@ -0,0 +1,60 @@
+use std::{collections::BTreeMap, sync::Arc};
+
+use crate::devices::ManagedDisplay;
+
+pub enum Broadcast<'a> {
+    ClearImage { page: u8 },
+    SetImageData { page: u8, data: &'a [u8; 0x38400] },
+    DisplayFile { page: u8, index: u8, file: u8 },
+    SetLeds { page: u8, value: bool },
+}
+
+// Named sets of displays, referenced by serial number so that they survive replugging
+#[derive(Default)]
+pub struct Groups {
+    groups: BTreeMap<String, Vec<String>>,
+}
+
+impl Groups {
+    pub fn set(&mut self, name: String, serial_numbers: Vec<String>) {
+        self.groups.insert(name, serial_numbers);
+    }
+
+    pub fn remove(&mut self, name: &str) -> bool {
+        self.groups.remove(name).is_some()
+    }
+
+    pub fn serial_numbers(&self, name: &str) -> Option<&[String]> {
+        self.groups.get(name).map(Vec::as_slice)
+    }
+}
+
+impl Broadcast<'_> {
+    pub fn apply(&self, display: &dyn ManagedDisplay) -> Result<(), ()> {
+        match *self {
+            Broadcast::ClearImage { page } => display.clear_image(page),
+            Broadcast::SetImageData { page, data } => display.set_image_data(page, data),
+            Broadcast::DisplayFile { page, index, file } => display.display_file(page, index, file),
+            Broadcast::SetLeds { page, value } => display
+                .leds()
+                .try_for_each(|index| display.set_led(page, index, value)),
+        }
+    }
+}
+
+// Returns the number of displays the operation has failed on
+pub fn broadcast(displays: &[Arc<dyn ManagedDisplay>], operation: &Broadcast) -> usize {
+    let mut failed = 0;
+    for display in displays {
+        // taken beforehand, as the device may be gone by the time the operation fails
+        let serial_number = display.serial_number();
+        if operation.apply(display.as_ref()).is_err() {
+            log::warn!(
+                "Broadcast operation has failed on device {:?}",
+                serial_number
+            );
+            failed += 1;
+        }
+    }
+    failed
+}
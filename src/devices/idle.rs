@@ -0,0 +1,156 @@
+use std::{
+    mem,
+    sync::{Mutex, MutexGuard, PoisonError},
+    time::{Duration, Instant},
+};
+
+#[derive(Clone, Debug)]
+pub enum IdleAction {
+    Blank,
+    CycleFiles { index: u8, files: Vec<u8> },
+    CycleImages,
+}
+
+#[derive(Clone, Debug)]
+pub struct IdleBehavior {
+    pub page: u8,
+    pub timeout: Duration,
+    pub interval: Duration,
+    pub action: IdleAction,
+}
+
+#[derive(Debug)]
+pub enum IdleStep {
+    Blank { page: u8 },
+    DisplayFile { page: u8, index: u8, file: u8 },
+    ShowCachedImage { page: u8, cached_page: u8 },
+}
+
+// Tracks client activity of a device and decides when (and what) to show while it's idle
+pub struct IdleWatch {
+    state: Mutex<IdleWatchState>,
+}
+
+struct IdleWatchState {
+    behavior: Option<IdleBehavior>,
+    last_activity: Instant,
+    idle: bool,
+    next_step_at: Instant,
+    position: usize,
+}
+
+impl Default for IdleWatch {
+    fn default() -> Self {
+        let now = Instant::now();
+        IdleWatch {
+            state: Mutex::new(IdleWatchState {
+                behavior: None,
+                last_activity: now,
+                idle: false,
+                next_step_at: now,
+                position: 0,
+            }),
+        }
+    }
+}
+
+impl IdleWatch {
+    // Steps run under the lock and may panic along with the device thread; the state is consistent
+    // by then (it's updated before running the step), so the poisoning is ignored
+    fn lock(&self) -> MutexGuard<'_, IdleWatchState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    // `on_wake` is called with the previous idle page if the device was idle, as with `touch`
+    pub fn set_behavior<F: FnOnce(u8)>(&self, behavior: Option<IdleBehavior>, on_wake: F) {
+        let mut state = self.lock();
+        let previous = mem::replace(&mut state.behavior, behavior);
+        state.last_activity = Instant::now();
+        let was_idle = mem::replace(&mut state.idle, false);
+        state.position = 0;
+        if let Some(previous) = previous.filter(|_| was_idle) {
+            log::debug!("Leaving idle, as the idle behavior has changed");
+            on_wake(previous.page);
+        }
+    }
+
+    // Records client activity; `on_wake` is called with the idle page if the device was idle
+    pub fn touch<F: FnOnce(u8)>(&self, on_wake: F) {
+        let mut state = self.lock();
+        state.last_activity = Instant::now();
+        if !state.idle {
+            return;
+        }
+        state.idle = false;
+        state.position = 0;
+        if let Some(ref behavior) = state.behavior {
+            log::debug!("Waking up from idle");
+            on_wake(behavior.page);
+        }
+    }
+
+    pub fn next_deadline(&self) -> Option<Instant> {
+        let state = self.lock();
+        let behavior = state.behavior.as_ref()?;
+        match state.idle {
+            false => Some(state.last_activity + behavior.timeout),
+            true => match behavior.action {
+                IdleAction::Blank => None, // nothing to do until woken up
+                _ => Some(state.next_step_at),
+            },
+        }
+    }
+
+    // Runs the due idle step (if any) while holding the idle state,
+    // so client activity can't interleave with it
+    pub fn run_due<F: FnOnce(IdleStep)>(&self, cached_pages: impl FnOnce() -> Vec<u8>, run: F) {
+        let mut state = self.lock();
+        let now = Instant::now();
+        let Some(ref behavior) = state.behavior else { return };
+        if !state.idle && now < state.last_activity + behavior.timeout {
+            return;
+        }
+        if state.idle && now < state.next_step_at {
+            return;
+        }
+
+        let page = behavior.page;
+        let interval = behavior.interval;
+        let position = state.position;
+        let step = match behavior.action {
+            IdleAction::Blank => match state.idle {
+                false => Some(IdleStep::Blank { page }),
+                true => None,
+            },
+            IdleAction::CycleFiles { index, ref files } => match files.is_empty() {
+                false => Some(IdleStep::DisplayFile {
+                    page,
+                    index,
+                    file: files[position % files.len()],
+                }),
+                true => None,
+            },
+            IdleAction::CycleImages => {
+                let cached_pages = cached_pages();
+                match cached_pages.is_empty() {
+                    false => Some(IdleStep::ShowCachedImage {
+                        page,
+                        cached_page: cached_pages[position % cached_pages.len()],
+                    }),
+                    true => None,
+                }
+            }
+        };
+
+        if !state.idle {
+            log::debug!("Device is idle, starting idle behavior");
+        }
+        state.idle = true;
+        state.position = position.wrapping_add(1);
+        state.next_step_at = now + interval;
+        if let Some(step) = step {
+            log::trace!("Running idle step: {:?}", step);
+            run(step);
+        }
+    }
+}
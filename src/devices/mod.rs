@@ -1,54 +1,136 @@
+mod callback_gate;
+mod groups;
+mod idle;
+mod packet_log;
 mod saitek_fip_lcd;
+mod stats;
+mod storage;
 mod usb_ids;
+mod usb_strings;
+
+pub use callback_gate::CallbackGate;
+pub use groups::{broadcast, Broadcast};
+pub use idle::{IdleAction, IdleBehavior};
+pub use stats::FrameStats;
+pub use storage::StorageUsage;
 
 use rusb::UsbContext;
 use std::{
     collections::BTreeMap,
     io::Read,
-    sync::{Arc, RwLock, Weak},
+    mem,
+    ops::RangeInclusive,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, PoisonError, RwLock, Weak,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
 };
 use uuid::Uuid;
 
+#[derive(Clone, Debug, Default)]
+pub struct DeviceStrings {
+    pub manufacturer: Option<String>,
+    pub product: Option<String>,
+    pub serial_number: Option<String>,
+}
+
+#[derive(Debug)]
+pub enum SaveFileError {
+    Read,
+    Device,
+    StorageFull,
+}
+
+// Called from the device thread after it has been restarted (and the device state replayed)
+pub type RestartHandler = Box<dyn Fn() + Send + Sync>;
+
+// Called from the device thread with the state of the device buttons
+pub type ButtonsHandler = Arc<dyn Fn(u32) + Send + Sync>;
+
 pub trait ManagedDisplay: Send + Sync {
     fn ready(&self) -> bool;
-    fn serial_number(&self) -> String;
+    // Whether the device initialization has finished, successfully (see `ready`) or not
+    fn settled(&self) -> bool;
+    // `None` if the device is not initialized yet or is gone
+    fn serial_number(&self) -> Option<String>;
+    fn device_strings(&self) -> Option<DeviceStrings>;
     fn device_type_uuid(&self) -> Uuid;
+    fn leds(&self) -> RangeInclusive<u8>;
+    fn set_idle_behavior(&self, behavior: Option<IdleBehavior>);
+    // Handlers are keyed by their owner, so several owners of the same display don't replace each other's
+    fn add_buttons_handler(&self, key: usize, handler: ButtonsHandler);
+    fn remove_buttons_handler(&self, key: usize);
+    // Stamps a frame counter into uploaded frames and collects their latency into `frame_stats`
+    fn set_latency_mode(&self, enabled: bool);
+    fn frame_stats(&self) -> FrameStats;
     fn set_image_data(&self, page: u8, data: &[u8; 0x38400]) -> Result<(), ()>;
     fn set_led(&self, page: u8, index: u8, value: bool) -> Result<(), ()>;
     fn clear_image(&self, page: u8) -> Result<(), ()>;
-    fn save_file(&self, page: u8, file: u8, data: &mut dyn Read) -> Result<(), ()>;
+    fn save_file(&self, page: u8, file: u8, data: &mut dyn Read) -> Result<(), SaveFileError>;
+    fn storage_usage(&self) -> StorageUsage;
     fn display_file(&self, page: u8, index: u8, file: u8) -> Result<(), ()>;
     fn delete_file(&self, page: u8, file: u8) -> Result<(), ()>;
+    // Refuses new commands and tells the device thread to stop, without waiting
+    fn stop(&self);
+    // Waits for the in-flight transfers and the device thread to finish (after `stop`), releasing the device
+    fn join(&self);
 }
 
 pub type UsbDeviceAddress = (u8, u8);
 
 pub struct State {
-    #[allow(dead_code)] // prevent dropping
-    libusb_context: rusb::Context,
-    #[allow(dead_code)] // prevent dropping
-    libusb_hotplug_reg: rusb::Registration<rusb::Context>,
+    libusb_context: Option<rusb::Context>,
+    libusb_hotplug_reg: Option<rusb::Registration<rusb::Context>>,
+    libusb_events_thread: Option<JoinHandle<()>>,
+    libusb_events_running: Arc<AtomicBool>,
+    libusb_deferred_teardown: Arc<Mutex<DeferredTeardown>>,
+    accepting_displays: Arc<AtomicBool>,
+    shut_down: bool,
     displays: Arc<RwLock<BTreeMap<UsbDeviceAddress, Arc<dyn ManagedDisplay>>>>,
     display_hotplug_handlers: Arc<RwLock<Vec<Box<dyn Hotplug>>>>,
+    callback_gate: Arc<CallbackGate>,
+    groups: RwLock<groups::Groups>,
+    settle_deadline: Instant,
+}
+
+// What a shutdown started from within a hotplug callback can't do itself (the callback runs
+// on the event thread, and the hotplug handler is owned by the registration), so it's handed
+// over to the event thread, to be done after the callback has returned
+#[derive(Default)]
+struct DeferredTeardown {
+    displays: Vec<Arc<dyn ManagedDisplay>>,
+    libusb_hotplug_reg: Option<rusb::Registration<rusb::Context>>,
 }
 
 pub trait Hotplug: Send + Sync {
     fn display_arrived(&mut self, device_addr: UsbDeviceAddress);
     fn display_left(&mut self, device_addr: UsbDeviceAddress);
+    // The device thread has panicked and has been restarted; the device is ready again
+    fn display_restarted(&mut self, _device_addr: UsbDeviceAddress) {}
 }
 
 struct UsbHotplugHandler {
     displays: Weak<RwLock<BTreeMap<UsbDeviceAddress, Arc<dyn ManagedDisplay>>>>,
     display_hotplug_handlers: Weak<RwLock<Vec<Box<dyn Hotplug>>>>,
+    callback_gate: Arc<CallbackGate>,
+    accepting_displays: Arc<AtomicBool>,
 }
 
-pub fn init() -> Result<State, ()> {
+pub fn init(settle_timeout: Duration) -> Result<State, ()> {
     let displays: Arc<RwLock<BTreeMap<UsbDeviceAddress, Arc<dyn ManagedDisplay>>>> =
         Arc::new(RwLock::new(BTreeMap::new()));
     let display_hotplug_handlers: Arc<RwLock<Vec<Box<dyn Hotplug>>>> =
         Arc::new(RwLock::new(Vec::with_capacity(1)));
+    let callback_gate: Arc<CallbackGate> = Arc::default();
+    let accepting_displays = Arc::new(AtomicBool::new(true));
+    let libusb_events_running = Arc::new(AtomicBool::new(true));
+    let libusb_deferred_teardown: Arc<Mutex<DeferredTeardown>> = Arc::default();
 
-    let libusb_context: rusb::Context = rusb::Context::new().expect("Cannot create libusb context");
+    // not panicking, as this is called from `extern` functions
+    let libusb_context: rusb::Context = rusb::Context::new()
+        .map_err(|err| log::error!("Cannot create libusb context: {}", err))?;
     let libusb_hotplug_reg = rusb::HotplugBuilder::new()
         .enumerate(true)
         .vendor_id(usb_ids::VID_SAITEK)
@@ -57,31 +139,76 @@ pub fn init() -> Result<State, ()> {
             Box::new(UsbHotplugHandler {
                 displays: Arc::downgrade(&displays),
                 display_hotplug_handlers: Arc::downgrade(&display_hotplug_handlers),
+                callback_gate: callback_gate.clone(),
+                accepting_displays: accepting_displays.clone(),
             }),
         )
-        .expect("Cannot register libusb hotplug handler");
+        .map_err(|err| log::error!("Cannot register libusb hotplug handler: {}", err))?;
 
     let _libusb_context = libusb_context.clone();
-    std::thread::Builder::new()
+    let _libusb_events_running = libusb_events_running.clone();
+    let _libusb_deferred_teardown = libusb_deferred_teardown.clone();
+    let libusb_events_thread = std::thread::Builder::new()
         .name("libusb events handling thread".to_owned())
-        .spawn(move || loop {
-            _libusb_context
-                .handle_events(None)
-                .expect("Cannot handle events (libusb)");
+        .spawn(move || {
+            // woken up by `interrupt_handle_events` on shutdown
+            while _libusb_events_running.load(Ordering::Acquire) {
+                _libusb_context
+                    .handle_events(None)
+                    .expect("Cannot handle events (libusb)");
+            }
+            let deferred_teardown = mem::take(
+                &mut *_libusb_deferred_teardown
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner),
+            );
+            // no events are handled by this thread anymore, so the device transfers handle them themselves
+            deferred_teardown.displays.iter().for_each(|display| display.join());
+            drop(deferred_teardown.displays);
+            drop(deferred_teardown.libusb_hotplug_reg);
+            log::trace!("Hotplug handler is deregistered");
         })
-        .expect("Cannot start libusb events handling thread");
+        .map_err(|err| log::error!("Cannot start libusb events handling thread: {}", err))?;
 
     Ok(State {
-        libusb_context,
-        libusb_hotplug_reg,
+        libusb_context: Some(libusb_context),
+        libusb_hotplug_reg: Some(libusb_hotplug_reg),
+        libusb_events_thread: Some(libusb_events_thread),
+        libusb_events_running,
+        libusb_deferred_teardown,
+        accepting_displays,
+        shut_down: false,
         displays,
         display_hotplug_handlers,
+        callback_gate,
+        groups: RwLock::default(),
+        // devices present at the start are enumerated synchronously by the hotplug registration,
+        // but are initialized asynchronously by their threads
+        settle_deadline: Instant::now() + settle_timeout,
     })
 }
 
+impl UsbHotplugHandler {
+    fn restart_handler(&self, addr: UsbDeviceAddress) -> RestartHandler {
+        let display_hotplug_handlers = self.display_hotplug_handlers.clone();
+        let callback_gate = self.callback_gate.clone();
+        Box::new(move || {
+            let Some(ref rc) = display_hotplug_handlers.upgrade() else { return; };
+            let Some(_guard) = callback_gate.enter() else { return; };
+            let mut handlers = rc.write().expect("State is poisoned");
+            handlers
+                .iter_mut()
+                .for_each(|handler| handler.display_restarted(addr))
+        })
+    }
+}
+
 impl<T: UsbContext + 'static> rusb::Hotplug<T> for UsbHotplugHandler {
     fn device_arrived(&mut self, device: rusb::Device<T>) {
         let addr = (device.bus_number(), device.address());
+        if !self.accepting_displays.load(Ordering::Acquire) {
+            return;
+        }
 
         let Ok(desc) = device.device_descriptor() else {
             log::warn!(
@@ -99,7 +226,7 @@ impl<T: UsbContext + 'static> rusb::Hotplug<T> for UsbHotplugHandler {
                     bus_number = device.bus_number(),
                     address = device.address()
                 );
-                crate::devices::saitek_fip_lcd::new_from_libusb(device)
+                crate::devices::saitek_fip_lcd::new_from_libusb(device, self.restart_handler(addr))
             }
             _ => return,
         };
@@ -107,10 +234,16 @@ impl<T: UsbContext + 'static> rusb::Hotplug<T> for UsbHotplugHandler {
         {
             let Some(ref rc) = self.displays.upgrade() else { return; };
             let mut displays = rc.write().expect("State is poisoned");
+            // checked under the lock, so the display can't slip past the shutdown
+            if !self.accepting_displays.load(Ordering::Acquire) {
+                display.stop();
+                return;
+            }
             displays.insert(addr, display);
         }
         {
             let Some(ref rc) = self.display_hotplug_handlers.upgrade() else { return; };
+            let Some(_guard) = self.callback_gate.enter() else { return; };
             let mut handlers = rc.write().expect("State is poisoned");
             handlers
                 .iter_mut()
@@ -134,6 +267,7 @@ impl<T: UsbContext + 'static> rusb::Hotplug<T> for UsbHotplugHandler {
         }
         {
             let Some(ref rc) = self.display_hotplug_handlers.upgrade() else { return; };
+            let Some(_guard) = self.callback_gate.enter() else { return; };
             let mut handlers = rc.write().expect("State is poisoned");
             handlers
                 .iter_mut()
@@ -143,10 +277,85 @@ impl<T: UsbContext + 'static> rusb::Hotplug<T> for UsbHotplugHandler {
 }
 
 impl State {
+    pub fn callback_gate(&self) -> Arc<CallbackGate> {
+        self.callback_gate.clone()
+    }
+
+    // Tears everything down in an order that can't hang on pending transfers:
+    // stop accepting commands -> wait for the in-flight transfers and join device threads
+    // -> deregister hotplug -> stop the event loop -> close the context.
+    // Safe to call from within callbacks (from within a hotplug one, the event thread finishes
+    // the teardown once it returns) and more than once
+    pub fn shutdown(&mut self) {
+        if self.shut_down {
+            return;
+        }
+        self.shut_down = true;
+        log::debug!("Shutting down");
+
+        self.callback_gate.close();
+        let displays: Vec<Arc<dyn ManagedDisplay>> = {
+            let mut displays = self.displays.write().unwrap_or_else(PoisonError::into_inner);
+            self.accepting_displays.store(false, Ordering::Release);
+            mem::take(&mut *displays).into_values().collect()
+        };
+        // stopping all of them first, so they wind down in parallel
+        displays.iter().for_each(|display| display.stop());
+
+        let on_events_thread = self
+            .libusb_events_thread
+            .as_ref()
+            .is_some_and(|handle| handle.thread().id() == thread::current().id());
+        {
+            let mut deferred_teardown = self
+                .libusb_deferred_teardown
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
+            if on_events_thread {
+                // it's the hotplug handler running now, holding the libusb events lock: device transfers
+                // can't complete until it returns
+                deferred_teardown.displays = displays;
+                log::trace!("Devices are left for the event thread");
+            } else {
+                displays.iter().for_each(|display| display.join());
+                drop(displays);
+                log::trace!("Devices are shut down");
+            }
+            // the event thread may be dispatching a hotplug event right now, so the handler is only
+            // deregistered by it, once its loop has exited
+            deferred_teardown.libusb_hotplug_reg = self.libusb_hotplug_reg.take();
+        }
+
+        self.libusb_events_running.store(false, Ordering::Release);
+        if let Some(ref libusb_context) = self.libusb_context {
+            libusb_context.interrupt_handle_events();
+        }
+        if let Some(libusb_events_thread) = self.libusb_events_thread.take() {
+            // the event thread can't wait for itself; it exits by itself as soon as the callback returns
+            if !on_events_thread && libusb_events_thread.join().is_err() {
+                log::error!("libusb events handling thread has panicked");
+            }
+        }
+        log::trace!("Event loop is stopped");
+
+        drop(self.libusb_context.take());
+        log::debug!("Shut down");
+    }
+
     pub fn add_hotplug_handler(&mut self, hotplug: Box<dyn Hotplug>) {
         self.display_hotplug_handlers.write().unwrap().push(hotplug);
     }
 
+    // Whether the devices present at the initialization have finished initializing
+    // (or the settle timeout has passed)
+    pub fn settled(&self) -> bool {
+        if Instant::now() >= self.settle_deadline {
+            return true;
+        }
+        let displays = self.displays.read().unwrap();
+        displays.values().all(|display| display.settled())
+    }
+
     pub fn display_addrs(&self) -> Vec<UsbDeviceAddress> {
         let displays = self.displays.read().unwrap();
         displays
@@ -162,4 +371,36 @@ impl State {
             None => None,
         }
     }
+
+    pub fn set_group(&self, name: String, serial_numbers: Vec<String>) {
+        self.groups.write().unwrap().set(name, serial_numbers);
+    }
+
+    pub fn remove_group(&self, name: &str) -> bool {
+        self.groups.write().unwrap().remove(name)
+    }
+
+    // Ready displays of the group; members that are not connected are skipped
+    pub fn group_displays(&self, name: &str) -> Option<Vec<Arc<dyn ManagedDisplay>>> {
+        let groups = self.groups.read().unwrap();
+        let serial_numbers = groups.serial_numbers(name)?;
+        let displays = self.displays.read().unwrap();
+        Some(
+            displays
+                .values()
+                .filter(|display| {
+                    display
+                        .serial_number()
+                        .is_some_and(|serial_number| serial_numbers.contains(&serial_number))
+                })
+                .cloned()
+                .collect(),
+        )
+    }
+}
+
+impl Drop for State {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
 }
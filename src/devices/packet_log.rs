@@ -0,0 +1,94 @@
+use std::{
+    collections::VecDeque,
+    fmt::Debug,
+    sync::Mutex,
+    thread,
+    time::Instant,
+};
+
+const PACKET_LOG_CAPACITY: usize = 32;
+
+#[derive(Debug)]
+pub enum PacketDirection {
+    Out,
+    In,
+}
+
+struct PacketLogEntry<P> {
+    at: Instant,
+    direction: PacketDirection,
+    packet: P,
+    data_len: usize,
+}
+
+// Last control packets exchanged with a device, dumped to the log when something goes wrong,
+// so bug reports carry the protocol context even without trace logging enabled
+pub struct PacketLog<P: Debug> {
+    entries: Mutex<VecDeque<PacketLogEntry<P>>>,
+}
+
+impl<P: Debug> Default for PacketLog<P> {
+    fn default() -> Self {
+        PacketLog {
+            entries: Mutex::new(VecDeque::with_capacity(PACKET_LOG_CAPACITY)),
+        }
+    }
+}
+
+impl<P: Debug> PacketLog<P> {
+    pub fn record(&self, direction: PacketDirection, packet: P, data_len: usize) {
+        // never let a poisoned log get in the way of talking to the device
+        let mut entries = match self.entries.lock() {
+            Ok(entries) => entries,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        if entries.len() == PACKET_LOG_CAPACITY {
+            entries.pop_front();
+        }
+        entries.push_back(PacketLogEntry {
+            at: Instant::now(),
+            direction,
+            packet,
+            data_len,
+        });
+    }
+
+    pub fn dump(&self, reason: &str) {
+        let entries = match self.entries.lock() {
+            Ok(entries) => entries,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        let now = Instant::now();
+        log::error!(
+            "{} - last {} control packet(s) exchanged with the device:",
+            reason,
+            entries.len()
+        );
+        entries.iter().for_each(|entry| {
+            log::error!(
+                "  -{:.3}s {:?} {:?} (+{} bytes of data)",
+                now.duration_since(entry.at).as_secs_f64(),
+                entry.direction,
+                entry.packet,
+                entry.data_len
+            )
+        });
+    }
+
+    // Dumps the log if dropped while the thread is panicking
+    pub fn panic_guard(&self) -> PacketLogPanicGuard<'_, P> {
+        PacketLogPanicGuard { log: self }
+    }
+}
+
+pub struct PacketLogPanicGuard<'a, P: Debug> {
+    log: &'a PacketLog<P>,
+}
+
+impl<P: Debug> Drop for PacketLogPanicGuard<'_, P> {
+    fn drop(&mut self) {
+        if thread::panicking() {
+            self.log.dump("Panicked while talking to the device");
+        }
+    }
+}
@@ -1,9 +1,15 @@
 use std::{
     cell::OnceCell,
+    collections::BTreeMap,
     io::Read,
     mem,
-    sync::{Arc, Mutex, RwLock, Weak},
-    time::Duration, thread::sleep,
+    ops::RangeInclusive,
+    panic::{self, AssertUnwindSafe},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex, PoisonError, RwLock, Weak,
+    },
+    time::{Duration, Instant}, thread::{self, sleep, JoinHandle},
 };
 
 use bitmask_enum::bitmask;
@@ -11,7 +17,22 @@ use num_enum::{IntoPrimitive, TryFromPrimitive, TryFromPrimitiveError};
 use uuid::{self, Uuid};
 use zerocopy::{AsBytes, FromBytes, Unaligned};
 
-use crate::devices::ManagedDisplay;
+use crate::devices::{
+    idle::{IdleBehavior, IdleStep, IdleWatch},
+    packet_log::{PacketDirection, PacketLog},
+    stats::{self, FrameStats},
+    storage::{StorageEstimate, StorageUsage},
+    usb_strings, ButtonsHandler, DeviceStrings, ManagedDisplay, RestartHandler, SaveFileError,
+};
+
+// Error codes the device responds with, in `header_error`/`request_error`
+// (HRESULTs, the same as DirectOutput reports to apps in SRequestStatus)
+const DEVICE_E_DISKFULL: u32 = 0x80070070;
+const DEVICE_E_INVALIDARG: u32 = 0x80070057;
+const DEVICE_E_PAGENOTACTIVE: u32 = 0xff040001;
+
+// consecutive restarts of a panicking device thread before giving up on the device
+const MAX_RESTARTS: u32 = 3;
 
 struct DeviceHandlerWrapper<T: rusb::UsbContext> {
     libusb_handle: rusb::DeviceHandle<T>,
@@ -58,12 +79,35 @@ impl<T: rusb::UsbContext> DeviceHandlerWrapper<T> {
 struct UsbSaitekFipLcdInt<T: rusb::UsbContext> {
     handle: DeviceHandlerWrapper<T>,
     serial_number: String,
+    strings: DeviceStrings,
     device_type_uuid: Uuid,
     vendor_if_mutex: Mutex<()>,
+    packet_log: PacketLog<ControlPacket>,
 }
 struct UsbSaitekFipLcd<T: rusb::UsbContext> {
     libusb_device: rusb::Device<T>,
     int: Arc<RwLock<Option<UsbSaitekFipLcdInt<T>>>>,
+    idle: IdleWatch,
+    image_cache: Mutex<BTreeMap<u8, Box<[u8; 0x38400]>>>,
+    led_cache: Mutex<BTreeMap<(u8, u8), bool>>,
+    buttons_handlers: Mutex<BTreeMap<usize, ButtonsHandler>>,
+    latency_mode: AtomicBool,
+    frame_stats: Mutex<FrameStats>,
+    frame_counter: AtomicU64,
+    settled: AtomicBool,
+    restart_handler: RestartHandler,
+    storage: Mutex<StorageEstimate>,
+    stopping: AtomicBool,
+    worker: Mutex<Option<JoinHandle<()>>>,
+}
+
+// Marks the device as settled when its initialization is over, however it ends (including panics)
+struct SettledGuard<'a>(&'a AtomicBool);
+
+impl Drop for SettledGuard<'_> {
+    fn drop(&mut self) {
+        self.0.store(true, Ordering::Release);
+    }
 }
 
 impl<T: rusb::UsbContext> UsbSaitekFipLcdInt<T> {
@@ -93,14 +137,14 @@ impl<T: rusb::UsbContext> UsbSaitekFipLcdInt<T> {
         _ = libusb_handle.detach_kernel_driver(vendor_interface.number());
         libusb_handle.claim_interface(vendor_interface.number())?;
 
-        let serial_number = {
-            let langs = libusb_handle.read_languages(std::time::Duration::from_secs(5))?;
-            libusb_handle
-                .read_serial_number_string(
-                    langs[0],
-                    &device_descriptor,
-                    std::time::Duration::from_secs(1),
-                )?
+        let strings = usb_strings::read_device_strings(
+            &libusb_handle,
+            &device_descriptor,
+            std::time::Duration::from_secs(1),
+        );
+        let Some(serial_number) = strings.serial_number.clone() else {
+            log::error!("Could not read serial number of the device");
+            return Err(rusb::Error::NotFound);
         };
 
         // seems like that is just a harcoded uuid
@@ -137,8 +181,9 @@ impl<T: rusb::UsbContext> UsbSaitekFipLcdInt<T> {
             });
 
         log::info!(
-            "Saitek FIP device initialized (serial number: {:?}, type uuid: {:?})",
+            "Saitek FIP device initialized (serial number: {:?}, product: {:?}, type uuid: {:?})",
             serial_number,
+            strings.product,
             device_type_uuid
         );
 
@@ -156,15 +201,17 @@ impl<T: rusb::UsbContext> UsbSaitekFipLcdInt<T> {
                     .expect("Could not find OUT endpoint"),
             },
             serial_number,
+            strings,
             device_type_uuid,
             vendor_if_mutex: Mutex::default(),
+            packet_log: PacketLog::default(),
         })
     }
 }
 
 type BEU32 = zerocopy::byteorder::U32<zerocopy::byteorder::BigEndian>;
 
-#[derive(AsBytes, Debug, FromBytes, Unaligned)]
+#[derive(AsBytes, Clone, Debug, FromBytes, Unaligned)]
 #[repr(C)]
 struct ControlPacket {
     server_id: BEU32,
@@ -283,6 +330,21 @@ impl ControlPacket {
         self.header_error() > 0 || self.request_error() > 0
     }
 
+    // Whether the error is the device being out of space, if it can be told from the error codes
+    fn storage_full(&self) -> Option<bool> {
+        let errors = [self.header_error(), self.request_error()];
+        if errors.contains(&DEVICE_E_DISKFULL) {
+            return Some(true);
+        }
+        match errors
+            .iter()
+            .all(|error| matches!(*error, 0 | DEVICE_E_INVALIDARG | DEVICE_E_PAGENOTACTIVE))
+        {
+            true => Some(false),
+            false => None, // unknown
+        }
+    }
+
     fn new(request: Request) -> ControlPacket {
         ControlPacket {
             server_id: 0.into(),
@@ -366,8 +428,26 @@ impl<T: rusb::UsbContext> UsbSaitekFipLcdInt<T> {
         data: Option<&[u8]>,
     ) -> Result<(ControlPacket, Option<Vec<u8>>), rusb::Error> {
         let mutex = self.vendor_if_mutex.lock();
-        self._write(control_packet, data)?;
-        self._read()
+        let _dump_on_panic = self.packet_log.panic_guard();
+        self.packet_log.record(
+            PacketDirection::Out,
+            control_packet.clone(),
+            data.map_or(0, <[u8]>::len),
+        );
+        let result = self
+            ._write(control_packet, data)
+            .and_then(|_| self._read());
+        match result {
+            Ok((ref packet, ref data)) => self.packet_log.record(
+                PacketDirection::In,
+                packet.clone(),
+                data.as_ref().map_or(0, Vec::len),
+            ),
+            Err(err) => self
+                .packet_log
+                .dump(&format!("Could not transcieve with the device ({})", err)),
+        };
+        result
     }
 }
 
@@ -393,15 +473,179 @@ impl<T: rusb::UsbContext> UsbSaitekFipLcd<T> {
         control_packet: ControlPacket,
         data: Option<&[u8]>,
     ) -> Result<(ControlPacket, Option<Vec<u8>>), rusb::Error> {
+        if self.stopping.load(Ordering::Acquire) {
+            return Err(rusb::Error::Interrupted);
+        }
         let int_guard = self.int.read().expect("Device is poisoned");
-        let int = int_guard
-            .as_ref()
-            .expect("Device is gone or not initialized yet");
+        let Some(int) = int_guard.as_ref() else {
+            log::error!("Device is gone or not initialized yet");
+            return Err(rusb::Error::NoDevice);
+        };
         int.transcieve(control_packet, data)
     }
 
-    fn _thread_target(device_weak: Weak<UsbSaitekFipLcd<T>>) {
+    fn check_response(&self, packet: &ControlPacket) -> Result<(), ()> {
+        if !packet.has_error() {
+            return Ok(());
+        }
+        if let Some(ref int) = *self.int.read().expect("Device is poisoned") {
+            int.packet_log.dump("Device has responded with an error");
+        }
+        Err(()) // TODO
+    }
+
+    fn _set_image_data(&self, page: u8, data: &[u8; 0x38400]) -> Result<(), ()> {
+        let mut packet = ControlPacket::new(Request::SetImage);
+        packet.set_page(page);
+        packet.set_data_size(data.len());
+        let (packet, _) = self.transmit(packet, Some(data)).map_err(|_| ())?; // TODO: error
+        self.check_response(&packet)
+    }
+
+    fn _clear_image(&self, page: u8) -> Result<(), ()> {
+        let mut packet = ControlPacket::new(Request::ClearImage);
+        packet.set_page(page);
+        let (packet, _) = self.transmit(packet, None).map_err(|_| ())?; // TODO: error
+        self.check_response(&packet)
+    }
+
+    fn _display_file(&self, page: u8, index: u8, file: u8) -> Result<(), ()> {
+        let mut packet = ControlPacket::new(Request::SetImageFile);
+        packet.set_param_1(page.into());
+        packet.set_param_2(index.into());
+        packet.set_param_3(file.into());
+        let (packet, _) = self.transmit(packet, None).map_err(|_| ())?; // TODO: error
+        self.check_response(&packet)
+    }
+
+    fn cached_image(&self, page: u8) -> Option<Box<[u8; 0x38400]>> {
+        let cache = self.image_cache.lock().expect("Device is poisoned");
+        cache.get(&page).cloned()
+    }
+
+    // Must be called on every client command, so the idle behavior is interrupted
+    fn wake(&self) {
+        self.idle.touch(|page| self.restore_after_idle(page));
+    }
+
+    fn restore_after_idle(&self, page: u8) {
+        let result = match self.cached_image(page) {
+            Some(data) => self._set_image_data(page, &data),
+            None => self._clear_image(page),
+        };
+        if result.is_err() {
+            log::warn!("Could not restore page {} after being idle", page);
+        }
+    }
+
+    fn run_idle_step(&self) {
+        self.idle.run_due(
+            || {
+                let cache = self.image_cache.lock().expect("Device is poisoned");
+                cache.keys().copied().collect()
+            },
+            |step| {
+                let result = match step {
+                    IdleStep::Blank { page } => self._clear_image(page),
+                    IdleStep::DisplayFile { page, index, file } => {
+                        self._display_file(page, index, file)
+                    }
+                    IdleStep::ShowCachedImage { page, cached_page } => {
+                        match self.cached_image(cached_page) {
+                            Some(data) => self._set_image_data(page, &data),
+                            None => Ok(()),
+                        }
+                    }
+                };
+                if result.is_err() {
+                    log::warn!("Could not perform idle step");
+                }
+            },
+        );
+    }
+
+    fn _set_led(&self, page: u8, index: u8, value: bool) -> Result<(), ()> {
+        let mut packet = ControlPacket::new(Request::SetLed);
+        packet.set_param_1(page.into());
+        packet.set_param_2(index.into());
+        packet.set_param_3(value.into());
+        let (packet, _) = self.transmit(packet, None).map_err(|_| ())?; // TODO: error
+        self.check_response(&packet)
+    }
+
+    // Brings the device back to what the clients have set, after the device thread restart
+    fn replay_state(&self) {
+        let pages: Vec<u8> = {
+            let cache = self.image_cache.lock().expect("Device is poisoned");
+            cache.keys().copied().collect()
+        };
+        for page in pages {
+            if let Some(data) = self.cached_image(page) && self._set_image_data(page, &data).is_err() {
+                log::warn!("Could not restore image of page {}", page);
+            }
+        }
+        let leds = self.led_cache.lock().expect("Device is poisoned").clone();
+        for ((page, index), value) in leds {
+            if self._set_led(page, index, value).is_err() {
+                log::warn!("Could not restore LED {} of page {}", index, page);
+            }
+        }
+    }
+
+    // Drops whatever the panicked device thread has left behind, so it can be started anew
+    fn reset_after_panic(&self) {
+        let mut int = self.int.write().unwrap_or_else(PoisonError::into_inner);
+        if let Some(ref int) = *int {
+            int.packet_log.dump("Device thread has panicked");
+        }
+        drop(int.take());
+        drop(int);
+        self.int.clear_poison();
+        self.image_cache.clear_poison();
+        self.led_cache.clear_poison();
+        self.buttons_handlers.clear_poison();
+        self.frame_stats.clear_poison();
+        self.storage.clear_poison();
+    }
+
+    fn _supervise(device_weak: Weak<UsbSaitekFipLcd<T>>) {
+        let mut restarts = 0;
+        loop {
+            let started_at = Instant::now();
+            let thread_device_weak = device_weak.clone();
+            let result = panic::catch_unwind(AssertUnwindSafe(|| {
+                UsbSaitekFipLcd::_thread_target(thread_device_weak, restarts > 0)
+            }));
+            if result.is_ok() {
+                return;
+            }
+
+            let Some(device) = device_weak.upgrade() else { return }; // device is dropped
+            device.reset_after_panic();
+            if device.stopping.load(Ordering::Acquire) {
+                return;
+            }
+            if started_at.elapsed() > Duration::from_secs(60) {
+                restarts = 0; // it has been working for a while, so it's not a restart loop
+            }
+            if restarts >= MAX_RESTARTS {
+                log::error!("Device thread keeps panicking, giving up on the device");
+                return;
+            }
+            restarts += 1;
+            log::warn!(
+                "Device thread has panicked, restarting it ({}/{})",
+                restarts,
+                MAX_RESTARTS
+            );
+            drop(device);
+            sleep(Duration::from_secs(1));
+        }
+    }
+
+    fn _thread_target(device_weak: Weak<UsbSaitekFipLcd<T>>, restarted: bool) {
         let Some(device) = device_weak.upgrade() else { return };
+        let settled_guard = SettledGuard(&device.settled);
         let device_int = match UsbSaitekFipLcdInt::new(&device) {
             Ok(device_int) => device_int,
             Err(rusb::Error::Access) => {
@@ -419,11 +663,22 @@ impl<T: rusb::UsbContext> UsbSaitekFipLcd<T> {
             return;
         }
 
-        _ = device
-            .int
-            .write()
-            .expect("Device is poisoned")
-            .replace(device_int);
+        {
+            let mut int = device.int.write().expect("Device is poisoned");
+            // checked under the lock, as `join` takes the device out under it too
+            if device.stopping.load(Ordering::Acquire) {
+                return;
+            }
+            _ = int.replace(device_int);
+        }
+        drop(settled_guard);
+
+        if restarted {
+            device.replay_state();
+            log::info!("Device thread has been restarted");
+            (device.restart_handler)();
+        }
+        drop(device);
 
         let mut hid_buffer: [u8; 2] = [0, 0];
 
@@ -432,38 +687,60 @@ impl<T: rusb::UsbContext> UsbSaitekFipLcd<T> {
                 Some(device) => device,
                 None => return, // device is dropped
             };
-            match device
-                .int
-                .read()
-                .expect("Device is poisoned")
-                .as_ref()
-                .unwrap()
-                .handle
-                .read_hid(&mut hid_buffer, Duration::from_secs(5))
-            {
+            if device.stopping.load(Ordering::Acquire) {
+                return;
+            }
+            // also bounds how long the shutdown waits for this thread
+            let hid_timeout = match device.idle.next_deadline() {
+                Some(deadline) => deadline
+                    .saturating_duration_since(Instant::now())
+                    .clamp(Duration::from_millis(10), Duration::from_secs(1)),
+                None => Duration::from_secs(1),
+            };
+            // not matching on it directly, so the device lock is released before handling the result
+            let hid_result = match device.int.read().expect("Device is poisoned").as_ref() {
+                Some(int) => int.handle.read_hid(&mut hid_buffer, hid_timeout),
+                None => return, // device is invalidated
+            };
+            match hid_result {
                 Ok(_) => {
                     let buttons = Buttons::from(
                         <zerocopy::U16<zerocopy::BigEndian>>::from_bytes(hid_buffer).get(),
                     );
                     log::debug!("Got HID buttons: {:#?}", buttons);
-                    // TODO
-                }
-                Err(rusb::Error::Timeout) => {
-                    continue;
+                    let handlers: Vec<ButtonsHandler> = device
+                        .buttons_handlers
+                        .lock()
+                        .expect("Device is poisoned")
+                        .values()
+                        .cloned()
+                        .collect();
+                    handlers
+                        .iter()
+                        .for_each(|handler| handler(buttons.bits().into()));
                 }
+                Err(rusb::Error::Timeout) => {}
                 Err(rusb::Error::NoDevice) => {
                     log::info!("Device is disconnected, invalidating it");
                     if let Ok(mut guard) = device.int.write() {
                         drop(guard.take()); // invalidate the device
                     }
+                    return;
                 }
                 Err(err) => {
                     log::error!("Could not read from device ({}), invalidating it", err);
                     if let Ok(mut guard) = device.int.write() {
+                        if let Some(ref int) = *guard {
+                            int.packet_log.dump("Device is being invalidated");
+                        }
                         drop(guard.take()); // invalidate the device
                     }
+                    return;
                 }
             };
+            if device.ready() {
+                device.run_idle_step();
+            }
             drop(device);
         }
     }
@@ -471,21 +748,39 @@ impl<T: rusb::UsbContext> UsbSaitekFipLcd<T> {
 
 pub fn new_from_libusb<T: rusb::UsbContext + 'static>(
     libusb_device: rusb::Device<T>,
+    restart_handler: RestartHandler,
 ) -> Arc<dyn ManagedDisplay> {
     let device = Arc::new(UsbSaitekFipLcd {
         libusb_device: libusb_device.clone(),
         int: Arc::default(),
+        idle: IdleWatch::default(),
+        image_cache: Mutex::default(),
+        led_cache: Mutex::default(),
+        buttons_handlers: Mutex::default(),
+        latency_mode: AtomicBool::new(false),
+        frame_stats: Mutex::default(),
+        frame_counter: AtomicU64::new(0),
+        settled: AtomicBool::new(false),
+        restart_handler,
+        storage: Mutex::default(),
+        stopping: AtomicBool::new(false),
+        worker: Mutex::default(),
     });
 
     let device_ref = Arc::downgrade(&device);
-    std::thread::Builder::new()
+    let worker = std::thread::Builder::new()
         .name(format!(
             "Saitek FIP @ {:03}-{:03}",
             libusb_device.bus_number(),
             libusb_device.address()
         ))
-        .spawn(|| UsbSaitekFipLcd::_thread_target(device_ref))
+        .spawn(|| UsbSaitekFipLcd::_supervise(device_ref))
         .expect("Could not start device thread");
+    _ = device
+        .worker
+        .lock()
+        .expect("Device is poisoned")
+        .replace(worker);
 
     device
 }
@@ -495,12 +790,18 @@ impl<T: rusb::UsbContext> ManagedDisplay for UsbSaitekFipLcd<T> {
         self.int.read().is_ok_and(|int| int.is_some())
     }
 
-    fn serial_number(&self) -> String {
+    fn settled(&self) -> bool {
+        self.settled.load(Ordering::Acquire)
+    }
+
+    fn serial_number(&self) -> Option<String> {
         let int_guard = self.int.read().expect("Device is poisoned");
-        let int = int_guard
-            .as_ref()
-            .expect("Device is gone or not initialized yet");
-        int.serial_number.clone()
+        int_guard.as_ref().map(|int| int.serial_number.clone())
+    }
+
+    fn device_strings(&self) -> Option<DeviceStrings> {
+        let int_guard = self.int.read().expect("Device is poisoned");
+        int_guard.as_ref().map(|int| int.strings.clone())
     }
 
     fn device_type_uuid(&self) -> Uuid {
@@ -511,40 +812,85 @@ impl<T: rusb::UsbContext> ManagedDisplay for UsbSaitekFipLcd<T> {
         int.device_type_uuid
     }
 
+    fn leds(&self) -> RangeInclusive<u8> {
+        1..=8 // S1-S6 soft buttons, up and down
+    }
+
+    fn set_idle_behavior(&self, behavior: Option<IdleBehavior>) {
+        self.idle.set_behavior(behavior, |page| self.restore_after_idle(page));
+    }
+
+    fn set_latency_mode(&self, enabled: bool) {
+        self.latency_mode.store(enabled, Ordering::Relaxed);
+        if enabled {
+            *self.frame_stats.lock().expect("Device is poisoned") = FrameStats::default();
+            self.frame_counter.store(0, Ordering::Relaxed);
+        }
+    }
+
+    fn frame_stats(&self) -> FrameStats {
+        *self.frame_stats.lock().expect("Device is poisoned")
+    }
+
+    fn add_buttons_handler(&self, key: usize, handler: ButtonsHandler) {
+        self.buttons_handlers
+            .lock()
+            .expect("Device is poisoned")
+            .insert(key, handler);
+    }
+
+    fn remove_buttons_handler(&self, key: usize) {
+        self.buttons_handlers
+            .lock()
+            .expect("Device is poisoned")
+            .remove(&key);
+    }
+
     fn set_image_data(&self, page: u8, data: &[u8; 0x38400]) -> Result<(), ()> {
-        let mut packet = ControlPacket::new(Request::SetImage);
-        packet.set_page(page);
-        packet.set_data_size(data.len());
-        let (packet, _) = self.transmit(packet, Some(data)).map_err(|_| ())?; // TODO: error
-        match packet.has_error() {
-            false => Ok(()),
-            true => Err(()), // TODO
+        self.wake();
+        self.image_cache
+            .lock()
+            .expect("Device is poisoned")
+            .insert(page, Box::new(*data));
+        if !self.latency_mode.load(Ordering::Relaxed) {
+            return self._set_image_data(page, data);
         }
+
+        let mut stamped = Box::new(*data);
+        let counter = self.frame_counter.fetch_add(1, Ordering::Relaxed);
+        stats::stamp_frame_counter(&mut stamped, counter);
+        // not counting the waking up, which may re-upload a cached frame
+        let submitted_at = Instant::now();
+        let result = self._set_image_data(page, &stamped);
+        let latency = submitted_at.elapsed();
+        log::trace!("Frame {} latency: {:?}", counter, latency);
+        self.frame_stats
+            .lock()
+            .expect("Device is poisoned")
+            .record(latency, result.is_ok());
+        result
     }
 
     fn set_led(&self, page: u8, index: u8, value: bool) -> Result<(), ()> {
-        let mut packet = ControlPacket::new(Request::SetLed);
-        packet.set_param_1(page.into());
-        packet.set_param_2(index.into());
-        packet.set_param_3(value.into());
-        let (packet, _) = self.transmit(packet, None).map_err(|_| ())?; // TODO: error
-        match packet.has_error() {
-            false => Ok(()),
-            true => Err(()), // TODO
-        }
+        self.wake();
+        self.led_cache
+            .lock()
+            .expect("Device is poisoned")
+            .insert((page, index), value);
+        self._set_led(page, index, value)
     }
 
     fn clear_image(&self, page: u8) -> Result<(), ()> {
-        let mut packet = ControlPacket::new(Request::ClearImage);
-        packet.set_page(page);
-        let (packet, _) = self.transmit(packet, None).map_err(|_| ())?; // TODO: error
-        match packet.has_error() {
-            false => Ok(()),
-            true => Err(()), // TODO
-        }
+        self.wake();
+        self.image_cache
+            .lock()
+            .expect("Device is poisoned")
+            .remove(&page);
+        self._clear_image(page)
     }
 
-    fn save_file(&self, page: u8, file: u8, data: &mut dyn Read) -> Result<(), ()> {
+    fn save_file(&self, page: u8, file: u8, data: &mut dyn Read) -> Result<(), SaveFileError> {
+        self.wake();
         let mut packet = ControlPacket::new(Request::SaveFile);
         packet.set_param_1(page.into());
         packet.set_param_3(file.into());
@@ -552,39 +898,76 @@ impl<T: rusb::UsbContext> ManagedDisplay for UsbSaitekFipLcd<T> {
         let mut buffer = Vec::new();
         if let Err(err) = data.read_to_end(&mut buffer) {
             log::error!("Cannot read data: {:?}", err);
-            return Err(());
+            return Err(SaveFileError::Read);
         }
         packet.set_data_size(buffer.len());
 
         let (packet, _) = self
             .transmit(packet, Some(buffer.as_slice()))
-            .map_err(|_| ())?; // TODO: error
-        match packet.has_error() {
-            false => Ok(()),
-            true => Err(()), // TODO
+            .map_err(|_| SaveFileError::Device)?; // TODO: error
+        let mut storage = self.storage.lock().expect("Device is poisoned");
+        if !packet.has_error() {
+            storage.file_saved(page, file, buffer.len());
+            return Ok(());
         }
+        if storage.file_rejected(page, file, buffer.len(), packet.storage_full()) {
+            log::error!(
+                "Device storage is full (file of {} bytes rejected, {:?})",
+                buffer.len(),
+                storage.usage()
+            );
+            return Err(SaveFileError::StorageFull);
+        }
+        drop(storage);
+        _ = self.check_response(&packet);
+        Err(SaveFileError::Device)
     }
 
-    fn display_file(&self, page: u8, index: u8, file: u8) -> Result<(), ()> {
-        let mut packet = ControlPacket::new(Request::SaveFile);
-        packet.set_param_1(page.into());
-        packet.set_param_2(index.into());
-        packet.set_param_3(file.into());
-        let (packet, _) = self.transmit(packet, None).map_err(|_| ())?; // TODO: error
-        match packet.has_error() {
-            false => Ok(()),
-            true => Err(()), // TODO
+    fn storage_usage(&self) -> StorageUsage {
+        self.storage.lock().expect("Device is poisoned").usage()
+    }
+
+    fn stop(&self) {
+        self.stopping.store(true, Ordering::Release);
+    }
+
+    fn join(&self) {
+        // in-flight transfers (including the device thread HID read) hold the device lock
+        let mut int = self.int.write().unwrap_or_else(PoisonError::into_inner);
+        drop(int.take());
+        drop(int);
+
+        let worker = self
+            .worker
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take();
+        let Some(worker) = worker else { return };
+        // can't wait for itself (shutdown from within a buttons callback); it stops as soon as the callback returns
+        if worker.thread().id() == thread::current().id() {
+            return;
+        }
+        if worker.join().is_err() {
+            log::error!("Device thread has panicked while stopping");
         }
     }
 
+    fn display_file(&self, page: u8, index: u8, file: u8) -> Result<(), ()> {
+        self.wake();
+        self._display_file(page, index, file)
+    }
+
     fn delete_file(&self, page: u8, file: u8) -> Result<(), ()> {
-        let mut packet = ControlPacket::new(Request::SaveFile);
+        self.wake();
+        let mut packet = ControlPacket::new(Request::DeleteFile);
         packet.set_param_1(page.into());
         packet.set_param_3(file.into());
         let (packet, _) = self.transmit(packet, None).map_err(|_| ())?; // TODO: error
-        match packet.has_error() {
-            false => Ok(()),
-            true => Err(()), // TODO
-        }
+        self.check_response(&packet)?;
+        self.storage
+            .lock()
+            .expect("Device is poisoned")
+            .file_deleted(page, file);
+        Ok(())
     }
 }
@@ -0,0 +1,55 @@
+use std::time::Duration;
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FrameStats {
+    pub frames: u64,
+    pub failed_frames: u64,
+    pub last_latency: Duration,
+    pub min_latency: Duration,
+    pub max_latency: Duration,
+    pub total_latency: Duration,
+}
+
+impl FrameStats {
+    // `latency` is the time between the frame being submitted and the device acknowledging it
+    pub fn record(&mut self, latency: Duration, succeeded: bool) {
+        if !succeeded {
+            self.failed_frames += 1;
+            return;
+        }
+        self.min_latency = match self.frames {
+            0 => latency,
+            _ => self.min_latency.min(latency),
+        };
+        self.frames += 1;
+        self.last_latency = latency;
+        self.max_latency = self.max_latency.max(latency);
+        self.total_latency += latency;
+    }
+
+    pub fn avg_latency(&self) -> Duration {
+        match self.frames {
+            0 => Duration::ZERO,
+            frames => Duration::from_nanos((self.total_latency.as_nanos() / frames as u128) as u64),
+        }
+    }
+}
+
+const STAMP_CELLS: usize = 16;
+const STAMP_CELL_SIZE: usize = 4;
+const FRAME_WIDTH: usize = 320;
+const BYTES_PER_PIXEL: usize = 3;
+
+// Draws the lower 16 bits of the frame counter in the first rows of the frame
+// as a strip of black (0) and white (1) cells, most significant bit first,
+// so it can be read back from a camera recording of the display
+pub fn stamp_frame_counter(data: &mut [u8; 0x38400], counter: u64) {
+    for row in 0..STAMP_CELL_SIZE {
+        for cell in 0..STAMP_CELLS {
+            let bit = (counter >> (STAMP_CELLS - 1 - cell)) & 1;
+            let value = if bit == 1 { 0xff } else { 0x00 };
+            let start = (row * FRAME_WIDTH + cell * STAMP_CELL_SIZE) * BYTES_PER_PIXEL;
+            data[start..start + STAMP_CELL_SIZE * BYTES_PER_PIXEL].fill(value);
+        }
+    }
+}
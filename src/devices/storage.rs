@@ -0,0 +1,129 @@
+use std::collections::BTreeMap;
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct StorageUsage {
+    // bytes taken by the files saved (and not deleted) since the device has been connected
+    pub used: usize,
+    // the device flash capacity is not reported by the device, so it's inferred from the first
+    // "storage full" rejection: at least this many bytes fit
+    pub capacity_estimate: Option<usize>,
+}
+
+impl StorageUsage {
+    pub fn remaining_estimate(&self) -> Option<usize> {
+        self.capacity_estimate
+            .map(|capacity| capacity.saturating_sub(self.used))
+    }
+}
+
+#[derive(Default)]
+pub struct StorageEstimate {
+    files: BTreeMap<(u8, u8), usize>,
+    capacity_estimate: Option<usize>,
+}
+
+impl StorageEstimate {
+    fn used(&self) -> usize {
+        self.files.values().sum()
+    }
+
+    pub fn usage(&self) -> StorageUsage {
+        StorageUsage {
+            used: self.used(),
+            capacity_estimate: self.capacity_estimate,
+        }
+    }
+
+    pub fn file_saved(&mut self, page: u8, file: u8, size: usize) {
+        self.files.insert((page, file), size);
+    }
+
+    pub fn file_deleted(&mut self, page: u8, file: u8) {
+        self.files.remove(&(page, file));
+    }
+
+    // Records the device rejecting a file; `storage_full` is what the device error code tells,
+    // if it's a known one. An unknown code is only taken as the device being out of space if the file
+    // wouldn't fit into the capacity inferred earlier. Returns `true` if out of space
+    pub fn file_rejected(&mut self, page: u8, file: u8, size: usize, storage_full: Option<bool>) -> bool {
+        // a file saved under the same id is replaced, so it doesn't count
+        let used = self.used() - self.files.get(&(page, file)).copied().unwrap_or(0);
+        match storage_full {
+            Some(true) => {
+                // nothing is known about the files saved before the device has been connected,
+                // so an empty estimate doesn't tell anything
+                if used > 0 {
+                    self.capacity_estimate = Some(used);
+                }
+                true
+            }
+            Some(false) => false,
+            None => self
+                .capacity_estimate
+                .is_some_and(|capacity| used + size > capacity),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn full_reported_by_device() {
+        let mut storage = StorageEstimate::default();
+        storage.file_saved(1, 0, 100);
+        assert!(storage.file_rejected(1, 1, 50, Some(true)));
+        assert_eq!(storage.usage().capacity_estimate, Some(100));
+    }
+
+    #[test]
+    fn full_reported_by_device_with_nothing_stored() {
+        let mut storage = StorageEstimate::default();
+        assert!(storage.file_rejected(1, 0, 50, Some(true)));
+        assert_eq!(storage.usage().capacity_estimate, None);
+    }
+
+    #[test]
+    fn other_error_reported_by_device() {
+        let mut storage = StorageEstimate::default();
+        storage.file_saved(1, 0, 100);
+        assert!(!storage.file_rejected(1, 1, 50, Some(false)));
+        assert_eq!(storage.usage().capacity_estimate, None);
+    }
+
+    #[test]
+    fn unknown_error_without_estimate() {
+        let mut storage = StorageEstimate::default();
+        storage.file_saved(1, 0, 100);
+        assert!(!storage.file_rejected(1, 1, 50, None));
+        assert_eq!(storage.usage().capacity_estimate, None);
+    }
+
+    #[test]
+    fn unknown_error_exceeding_estimate() {
+        let mut storage = StorageEstimate::default();
+        storage.file_saved(1, 0, 100);
+        assert!(storage.file_rejected(1, 1, 50, Some(true)));
+        assert!(storage.file_rejected(1, 1, 50, None));
+        assert_eq!(storage.usage().capacity_estimate, Some(100));
+    }
+
+    #[test]
+    fn unknown_error_within_estimate() {
+        let mut storage = StorageEstimate::default();
+        storage.file_saved(1, 0, 100);
+        assert!(storage.file_rejected(1, 1, 50, Some(true)));
+        storage.file_deleted(1, 0);
+        assert!(!storage.file_rejected(1, 1, 50, None));
+    }
+
+    #[test]
+    fn replaced_file_not_counted() {
+        let mut storage = StorageEstimate::default();
+        storage.file_saved(1, 0, 100);
+        storage.file_saved(1, 1, 100);
+        assert!(storage.file_rejected(1, 1, 50, Some(true)));
+        assert_eq!(storage.usage().capacity_estimate, Some(100));
+    }
+}
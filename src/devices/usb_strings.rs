@@ -0,0 +1,71 @@
+use std::time::Duration;
+
+use crate::devices::DeviceStrings;
+
+const LANG_ID_EN_US: u16 = 0x0409;
+
+// Languages to try, in order: en-US first (if the device has it), then the rest as reported
+fn preferred_languages<T: rusb::UsbContext>(
+    handle: &rusb::DeviceHandle<T>,
+    timeout: Duration,
+) -> Vec<rusb::Language> {
+    let mut langs = match handle.read_languages(timeout) {
+        Ok(langs) => langs,
+        Err(err) => {
+            log::warn!("Could not read USB device supported languages ({})", err);
+            Vec::new()
+        }
+    };
+    langs.sort_by_key(|lang| lang.lang_id() != LANG_ID_EN_US);
+    langs
+}
+
+fn read_string<T: rusb::UsbContext>(
+    handle: &rusb::DeviceHandle<T>,
+    langs: &[rusb::Language],
+    index: Option<u8>,
+    timeout: Duration,
+) -> Option<String> {
+    let index = index?;
+    for lang in langs {
+        match handle.read_string_descriptor(*lang, index, timeout) {
+            Ok(string) => return Some(string),
+            Err(err) => log::debug!(
+                "Could not read USB string descriptor {} in language {:#06x} ({})",
+                index,
+                lang.lang_id(),
+                err
+            ),
+        }
+    }
+    // some devices don't report languages (or fail on all of them), but still respond to this
+    handle.read_string_descriptor_ascii(index).ok()
+}
+
+pub fn read_device_strings<T: rusb::UsbContext>(
+    handle: &rusb::DeviceHandle<T>,
+    device_descriptor: &rusb::DeviceDescriptor,
+    timeout: Duration,
+) -> DeviceStrings {
+    let langs = preferred_languages(handle, timeout);
+    DeviceStrings {
+        manufacturer: read_string(
+            handle,
+            &langs,
+            device_descriptor.manufacturer_string_index(),
+            timeout,
+        ),
+        product: read_string(
+            handle,
+            &langs,
+            device_descriptor.product_string_index(),
+            timeout,
+        ),
+        serial_number: read_string(
+            handle,
+            &langs,
+            device_descriptor.serial_number_string_index(),
+            timeout,
+        ),
+    }
+}
@@ -0,0 +1,314 @@
+// Small C API for using the panels directly, without DirectOutput semantics
+// (see `libfip/fip.h`)
+
+use core::slice;
+use std::{
+    collections::VecDeque,
+    ffi::CStr,
+    sync::{Arc, Mutex},
+    thread::sleep,
+    time::{Duration, Instant},
+};
+
+use crate::{devices, embed_addr, get_display, settle_timeout, STATE};
+
+// bumped on incompatible changes of the functions or structs below
+pub const FIP_API_VERSION: u32 = 1;
+
+pub const FIP_OK: i32 = 0;
+pub const FIP_E_INVALID_ARG: i32 = -1;
+pub const FIP_E_NO_DEVICE: i32 = -2;
+pub const FIP_E_DEVICE: i32 = -3;
+
+#[allow(non_camel_case_types)]
+type fip_buttons_callback =
+    unsafe extern "C" fn(device: *mut FipDevice, buttons: u32, ctx: *mut libc::c_void);
+
+const BUTTON_EVENTS_CAPACITY: usize = 64;
+
+pub struct FipDevice {
+    addr: devices::UsbDeviceAddress,
+    buttons: Arc<Mutex<ButtonsState>>,
+    // closed by `fip_close`, so the device isn't freed while its callback is running
+    callback_gate: Arc<devices::CallbackGate>,
+}
+
+#[derive(Default)]
+struct ButtonsState {
+    current: u32,
+    events: VecDeque<u32>,
+    callback: Option<(fip_buttons_callback, usize)>,
+}
+
+// Plain data, so it can be declared as-is via ctypes/cffi
+#[repr(C)]
+pub struct FipDeviceInfo {
+    serial_number: [u8; 64],
+    manufacturer: [u8; 64],
+    product: [u8; 64],
+    bus_number: u8,
+    address: u8,
+}
+
+// Devices opened via this API keep the library state alive;
+// the state is dropped with the last one, unless it was created by `DirectOutput_Initialize`
+struct Session {
+    open_devices: usize,
+    owns_state: bool,
+}
+
+static SESSION: Mutex<Session> = Mutex::new(Session {
+    open_devices: 0,
+    owns_state: false,
+});
+
+macro_rules! fip_export {
+    ($($toks: tt)+) => {
+        #[no_mangle]
+        pub unsafe extern "C" $($toks)+
+    };
+}
+
+fn session_acquire() -> Result<(), ()> {
+    let mut session = SESSION.lock().expect("Session is poisoned");
+    let mut state = STATE.lock().expect("State is poisoned");
+    if state.is_none() {
+        _ = pretty_env_logger::try_init();
+        state.replace(devices::init(settle_timeout())?);
+        session.owns_state = true;
+    }
+    session.open_devices += 1;
+    Ok(())
+}
+
+// Called when the state is (also) being used via the DirectOutput API, so it shouldn't be dropped by us
+pub fn disown_state() {
+    SESSION.lock().expect("Session is poisoned").owns_state = false;
+}
+
+fn session_release() {
+    let mut session = SESSION.lock().expect("Session is poisoned");
+    session.open_devices -= 1;
+    if session.open_devices > 0 || !session.owns_state {
+        return;
+    }
+    session.owns_state = false;
+    let state = STATE.lock().expect("State is poisoned").take();
+    if let Some(mut state) = state {
+        state.shutdown();
+        drop(state);
+        log::trace!("Last device closed, state dropped");
+    }
+}
+
+fn find_device(serial_number: Option<&str>) -> Option<devices::UsbDeviceAddress> {
+    let state = STATE.lock().expect("State is poisoned");
+    let state = state.as_ref()?;
+    state.display_addrs().into_iter().find(|addr| {
+        let Some(serial_number) = serial_number else { return true };
+        state
+            .display_by_addr(addr)
+            .is_some_and(|display| display.serial_number().as_deref() == Some(serial_number))
+    })
+}
+
+fn with_display<F: FnOnce(Arc<dyn devices::ManagedDisplay>) -> i32>(
+    device: *mut FipDevice,
+    f: F,
+) -> i32 {
+    let Some(device) = (unsafe { device.as_ref() }) else { return FIP_E_INVALID_ARG };
+    let display = {
+        let Some(ref state) = *STATE.lock().expect("State is poisoned") else {
+            return FIP_E_NO_DEVICE;
+        };
+        match get_display(state, embed_addr(device.addr)) {
+            Ok(display) => display,
+            Err(_) => return FIP_E_NO_DEVICE,
+        }
+    };
+    f(display)
+}
+
+// Buttons are both queued (for polling) and passed to the callback, if any
+fn install_buttons_handler(device: *mut FipDevice, display: Arc<dyn devices::ManagedDisplay>) -> i32 {
+    let Some(ref state) = *STATE.lock().expect("State is poisoned") else {
+        return FIP_E_NO_DEVICE;
+    };
+    let callback_gate = state.callback_gate();
+    let device_callback_gate = unsafe { &*device }.callback_gate.clone();
+    let buttons = unsafe { &*device }.buttons.clone();
+    let device_ptr = device as usize;
+    display.add_buttons_handler(device_ptr, Arc::new(move |value: u32| {
+        let callback = {
+            let mut buttons = buttons.lock().expect("Buttons state is poisoned");
+            buttons.current = value;
+            if buttons.events.len() == BUTTON_EVENTS_CAPACITY {
+                buttons.events.pop_front();
+            }
+            buttons.events.push_back(value);
+            buttons.callback
+        };
+        let Some((callback, ctx)) = callback else { return };
+        let Some(_guard) = callback_gate.enter() else { return };
+        let Some(_device_guard) = device_callback_gate.enter() else { return };
+        unsafe { callback(device_ptr as *mut FipDevice, value, ctx as *mut libc::c_void) };
+    }));
+    FIP_OK
+}
+
+fn copy_c_string(dst: &mut [u8], src: Option<&str>) {
+    dst.fill(0);
+    let Some(src) = src else { return };
+    // leave room for the terminating nul; truncation may split a UTF-8 sequence, but never the nul
+    let len = src.len().min(dst.len() - 1);
+    dst[..len].copy_from_slice(&src.as_bytes()[..len]);
+}
+
+fip_export! {
+    fn fip_api_version() -> u32 {
+        FIP_API_VERSION
+    }
+}
+
+fip_export! {
+    fn fip_open(serial_number: *const libc::c_char, timeout_ms: u32) -> *mut FipDevice {
+        let serial_number = match serial_number.is_null() {
+            true => None,
+            false => match unsafe { CStr::from_ptr(serial_number) }.to_str() {
+                Ok(serial_number) => Some(serial_number.to_owned()),
+                Err(_) => return std::ptr::null_mut(),
+            },
+        };
+        log::trace!("fip_open({:?}, {})", serial_number, timeout_ms);
+
+        if session_acquire().is_err() {
+            log::error!("Cannot perform library initialization");
+            return std::ptr::null_mut();
+        }
+
+        // devices show up asynchronously, especially right after the initialization
+        let deadline = Instant::now() + Duration::from_millis(timeout_ms.into());
+        loop {
+            if let Some(addr) = find_device(serial_number.as_deref()) {
+                let device = Box::into_raw(Box::new(FipDevice {
+                    addr,
+                    buttons: Arc::default(),
+                    callback_gate: Arc::default(),
+                }));
+                if with_display(device, |display| install_buttons_handler(device, display)) != FIP_OK {
+                    drop(unsafe { Box::from_raw(device) });
+                    session_release();
+                    return std::ptr::null_mut();
+                }
+                return device;
+            }
+            if Instant::now() >= deadline {
+                session_release();
+                return std::ptr::null_mut();
+            }
+            sleep(Duration::from_millis(50));
+        }
+    }
+}
+
+fip_export! {
+    fn fip_set_frame(device: *mut FipDevice, page: u8, frame: *const u8, frame_size: usize) -> i32 {
+        if frame.is_null() || frame_size != 0x38400 {
+            return FIP_E_INVALID_ARG;
+        }
+        let frame = unsafe { slice::from_raw_parts(frame, 0x38400) };
+        with_display(device, |display| {
+            match display.set_image_data(page, arrayref::array_ref![frame, 0, 0x38400]) {
+                Ok(()) => FIP_OK,
+                Err(()) => FIP_E_DEVICE,
+            }
+        })
+    }
+}
+
+fip_export! {
+    fn fip_clear_frame(device: *mut FipDevice, page: u8) -> i32 {
+        with_display(device, |display| match display.clear_image(page) {
+            Ok(()) => FIP_OK,
+            Err(()) => FIP_E_DEVICE,
+        })
+    }
+}
+
+fip_export! {
+    fn fip_set_led(device: *mut FipDevice, page: u8, index: u8, value: u8) -> i32 {
+        with_display(device, |display| {
+            if !display.leds().contains(&index) {
+                return FIP_E_INVALID_ARG;
+            }
+            match display.set_led(page, index, value != 0) {
+                Ok(()) => FIP_OK,
+                Err(()) => FIP_E_DEVICE,
+            }
+        })
+    }
+}
+
+fip_export! {
+    fn fip_get_info(device: *mut FipDevice, info: *mut FipDeviceInfo) -> i32 {
+        let Some(info) = (unsafe { info.as_mut() }) else { return FIP_E_INVALID_ARG };
+        with_display(device, |display| {
+            let Some(strings) = display.device_strings() else { return FIP_E_NO_DEVICE };
+            copy_c_string(&mut info.serial_number, strings.serial_number.as_deref());
+            copy_c_string(&mut info.manufacturer, strings.manufacturer.as_deref());
+            copy_c_string(&mut info.product, strings.product.as_deref());
+            let device = unsafe { &*device };
+            (info.bus_number, info.address) = device.addr;
+            FIP_OK
+        })
+    }
+}
+
+fip_export! {
+    fn fip_on_buttons(device: *mut FipDevice, callback: Option<fip_buttons_callback>, ctx: *mut libc::c_void) -> i32 {
+        let Some(device) = (unsafe { device.as_ref() }) else { return FIP_E_INVALID_ARG };
+        let mut buttons = device.buttons.lock().expect("Buttons state is poisoned");
+        buttons.callback = callback.map(|callback| (callback, ctx as usize));
+        FIP_OK
+    }
+}
+
+fip_export! {
+    fn fip_get_buttons(device: *mut FipDevice, buttons: *mut u32) -> i32 {
+        let Some(device) = (unsafe { device.as_ref() }) else { return FIP_E_INVALID_ARG };
+        let Some(buttons) = (unsafe { buttons.as_mut() }) else { return FIP_E_INVALID_ARG };
+        *buttons = device.buttons.lock().expect("Buttons state is poisoned").current;
+        FIP_OK
+    }
+}
+
+// Returns 1 and fills in `buttons` if there was a change of the buttons state since the last call, 0 otherwise
+fip_export! {
+    fn fip_poll_buttons(device: *mut FipDevice, buttons: *mut u32) -> i32 {
+        let Some(device) = (unsafe { device.as_ref() }) else { return FIP_E_INVALID_ARG };
+        let Some(buttons) = (unsafe { buttons.as_mut() }) else { return FIP_E_INVALID_ARG };
+        match device.buttons.lock().expect("Buttons state is poisoned").events.pop_front() {
+            Some(event) => {
+                *buttons = event;
+                1
+            }
+            None => 0,
+        }
+    }
+}
+
+fip_export! {
+    fn fip_close(device: *mut FipDevice) {
+        if device.is_null() {
+            return;
+        }
+        // waits for the callback if it's running on another thread
+        unsafe { &*device }.callback_gate.close();
+        with_display(device, |display| {
+            display.remove_buttons_handler(device as usize);
+            FIP_OK
+        });
+        drop(unsafe { Box::from_raw(device) });
+        session_release();
+    }
+}
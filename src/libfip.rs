@@ -6,11 +6,14 @@ use std::{
     fs,
     io::BufReader,
     sync::{Arc, Mutex},
+    thread::sleep,
+    time::Duration,
 };
 
 extern crate pretty_env_logger;
 
-mod devices;
+pub mod devices;
+mod fip;
 
 type PrgCtx = usize;
 type DevicePtr = u64;
@@ -42,6 +45,8 @@ pub const E_HANDLE: HRESULT = 0x80070006;
 pub const E_INVALIDARG: HRESULT = 0x80070057;
 pub const E_OUTOFMEMORY: HRESULT = 0x80007000e;
 pub const E_NOTIMPL: HRESULT = 0x80004001;
+pub const E_FAIL: HRESULT = 0x80004005;
+pub const E_DISKFULL: HRESULT = 0x80070070;
 // library errors
 pub const E_BUFFERTOOSMALL: HRESULT = 0xff04006f;
 pub const E_PAGENOTACTIVE: HRESULT = 0xff040001;
@@ -81,15 +86,50 @@ macro_rules! directoutputlib_export {
 
 static STATE: Mutex<Option<devices::State>> = Mutex::new(None);
 
+const DEFAULT_SETTLE_TIMEOUT: Duration = Duration::from_secs(2);
+
+// How long to wait for the devices present at the initialization to get ready
+fn settle_timeout() -> Duration {
+    let Ok(value) = std::env::var("LIBFIP_SETTLE_TIMEOUT_MS") else {
+        return DEFAULT_SETTLE_TIMEOUT;
+    };
+    match value.parse() {
+        Ok(timeout_ms) => Duration::from_millis(timeout_ms),
+        Err(_) => {
+            log::warn!("Invalid LIBFIP_SETTLE_TIMEOUT_MS value: {:?}", value);
+            DEFAULT_SETTLE_TIMEOUT
+        }
+    }
+}
+
+// Legacy apps tend to enumerate devices only once, right after the initialization,
+// so both wait (bounded by the settle timeout) until the devices present at the start are ready
+fn wait_settled() {
+    loop {
+        match *STATE.lock().expect("State is poisoned") {
+            Some(ref state) if !state.settled() => (),
+            _ => return,
+        }
+        sleep(Duration::from_millis(20));
+    }
+}
+
 directoutputlib_export! {
     fn DirectOutput_Initialize(app_name: *const libc::wchar_t) -> HRESULT {
-        pretty_env_logger::init();
+        _ = pretty_env_logger::try_init();
         log::trace!("DirectOutput_Initialize");
-        let mut state = STATE.lock().expect("State is poisoned");
-        if state.is_none() {
-            state.replace(devices::init().expect("Cannot perform library initialization"));
+        fip::disown_state();
+        {
+            let mut state = STATE.lock().expect("State is poisoned");
+            if state.is_none() {
+                let Ok(new_state) = devices::init(settle_timeout()) else {
+                    log::error!("Cannot perform library initialization");
+                    return E_FAIL;
+                };
+                state.replace(new_state);
+            }
         }
-        //sleep(Duration::from_secs(1));
+        wait_settled();
 
         if !app_name.is_null() && log::log_enabled!(log::Level::Info) {
             match unsafe { widestring::WideCStr::from_ptr_str(app_name.cast()) }.to_string() {
@@ -106,9 +146,12 @@ directoutputlib_export! {
     fn DirectOutput_Deinitialize() -> HRESULT {
         log::trace!("DirectOutput_Deinitialize");
 
-        let mut state = STATE.lock().expect("State is poisoned");
-        if state.is_some() {
-            drop(state.take());
+        // take the state out first, so callbacks still running on other threads
+        // won't block on `STATE` while we're waiting for them to finish
+        let state = STATE.lock().expect("State is poisoned").take();
+        if let Some(mut state) = state {
+            state.shutdown();
+            drop(state);
             log::trace!("App deinitialized, state dropped");
         }
 
@@ -161,6 +204,12 @@ impl devices::Hotplug for HotplugHandler {
             self.prg_ctx
         );
     }
+
+    // Reported as the device being replugged, so the app sets it up again
+    fn display_restarted(&mut self, addr: devices::UsbDeviceAddress) {
+        self.display_left(addr);
+        self.display_arrived(addr);
+    }
 }
 
 directoutputlib_export! {
@@ -178,12 +227,17 @@ directoutputlib_export! {
 
 directoutputlib_export! {
     fn DirectOutput_Enumerate(callback: Pfn_DirectOutput_EnumerateCallback, prg_ctx: PrgCtx) -> HRESULT {
-        let Some(ref state) = *STATE.lock().expect("State is poisoned") else {
-            log::error!("Library function has been called, but the library is not initialized");
-            return E_HANDLE;
+        wait_settled();
+        let (display_addrs, callback_gate) = {
+            let Some(ref state) = *STATE.lock().expect("State is poisoned") else {
+                log::error!("Library function has been called, but the library is not initialized");
+                return E_HANDLE;
+            };
+            (state.display_addrs(), state.callback_gate())
         };
 
-        state.display_addrs().iter().for_each(move |addr| {
+        display_addrs.iter().for_each(move |addr| {
+            let Some(_guard) = callback_gate.enter() else { return; };
             let device_ptr = embed_addr(*addr);
             log::trace!("Calling enumerate callback: {:p}({:#}, {:?})", callback, device_ptr, prg_ctx);
             unsafe { callback(device_ptr, prg_ctx); }
@@ -210,14 +264,16 @@ directoutputlib_export! {
 
 directoutputlib_export! {
     fn DirectOutput_GetDeviceType(device_ptr: DevicePtr, guid: *mut GUID) -> HRESULT {
-        let Some(ref state) = *STATE.lock().expect("State is poisoned") else {
-            log::error!("Library function has been called, but the library is not initialized");
-            return E_HANDLE;
-        };
-
-        let display = match get_display(state, device_ptr) {
-            Ok(display) => display,
-            Err(err) => return err,
+        // The state lock must not be held across device transfers
+        let display = {
+            let Some(ref state) = *STATE.lock().expect("State is poisoned") else {
+                log::error!("Library function has been called, but the library is not initialized");
+                return E_HANDLE;
+            };
+            match get_display(state, device_ptr) {
+                Ok(display) => display,
+                Err(err) => return err,
+            }
         };
 
         let uuid = display.device_type_uuid();
@@ -262,14 +318,16 @@ directoutputlib_export! {
 
 directoutputlib_export! {
     fn DirectOutput_SetLed(device_ptr: DevicePtr, page_number: DWORD, led_index: DWORD, led_value: DWORD) -> HRESULT {
-        let Some(ref state) = *STATE.lock().expect("State is poisoned") else {
-            log::error!("Library function has been called, but the library is not initialized");
-            return E_HANDLE;
-        };
-
-        let display = match get_display(state, device_ptr) {
-            Ok(display) => display,
-            Err(err) => return err,
+        // The state lock must not be held across device transfers
+        let display = {
+            let Some(ref state) = *STATE.lock().expect("State is poisoned") else {
+                log::error!("Library function has been called, but the library is not initialized");
+                return E_HANDLE;
+            };
+            match get_display(state, device_ptr) {
+                Ok(display) => display,
+                Err(err) => return err,
+            }
         };
 
         let Ok(page) = page_number.try_into() else { return E_INVALIDARG; };
@@ -295,14 +353,16 @@ directoutputlib_export! {
 
 directoutputlib_export! {
     fn DirectOutput_SetImage(device_ptr: DevicePtr, page_number: DWORD, image_index: DWORD, image_size: DWORD, image: *const u8) -> HRESULT {
-        let Some(ref state) = *STATE.lock().expect("State is poisoned") else {
-            log::error!("Library function has been called, but the library is not initialized");
-            return E_HANDLE;
-        };
-
-        let display = match get_display(state, device_ptr) {
-            Ok(display) => display,
-            Err(err) => return err,
+        // The state lock must not be held across device transfers
+        let display = {
+            let Some(ref state) = *STATE.lock().expect("State is poisoned") else {
+                log::error!("Library function has been called, but the library is not initialized");
+                return E_HANDLE;
+            };
+            match get_display(state, device_ptr) {
+                Ok(display) => display,
+                Err(err) => return err,
+            }
         };
 
         if image.is_null() {
@@ -359,14 +419,16 @@ directoutputlib_export! {
 
 directoutputlib_export! {
     fn DirectOutput_SaveFile(device_ptr: DevicePtr, page_number: DWORD, file_index: DWORD, filename_size: usize, filename: *const libc::wchar_t, status: *mut SRequestStatus) -> HRESULT {
-        let Some(ref state) = *STATE.lock().expect("State is poisoned") else {
-            log::error!("Library function has been called, but the library is not initialized");
-            return E_HANDLE;
-        };
-
-        let display = match get_display(state, device_ptr) {
-            Ok(display) => display,
-            Err(err) => return err,
+        // The state lock must not be held across device transfers
+        let display = {
+            let Some(ref state) = *STATE.lock().expect("State is poisoned") else {
+                log::error!("Library function has been called, but the library is not initialized");
+                return E_HANDLE;
+            };
+            match get_display(state, device_ptr) {
+                Ok(display) => display,
+                Err(err) => return err,
+            }
         };
 
         let Ok(filename_wide) = widestring::WideCStr::from_ptr(filename.cast(), filename_size) else {
@@ -378,24 +440,28 @@ directoutputlib_export! {
         };
         let Ok(page_number) = page_number.try_into() else { return E_INVALIDARG };
         let Ok(file_index) = file_index.try_into() else { return E_INVALIDARG };
-        _ = display.save_file(page_number, file_index, &mut BufReader::new(file));
-        // TODO: error handling
         // TODO: fill in `status`
-
-        S_OK
+        match display.save_file(page_number, file_index, &mut BufReader::new(file)) {
+            Ok(()) => S_OK,
+            Err(devices::SaveFileError::StorageFull) => E_DISKFULL,
+            Err(devices::SaveFileError::Read) => E_INVALIDARG,
+            Err(devices::SaveFileError::Device) => E_FAIL,
+        }
     }
 }
 
 directoutputlib_export! {
     fn DirectOutput_DisplayFile(device_ptr: DevicePtr, page_number: DWORD, image_index: DWORD, file_index: DWORD, status: *mut SRequestStatus) -> HRESULT {
-        let Some(ref state) = *STATE.lock().expect("State is poisoned") else {
-            log::error!("Library function has been called, but the library is not initialized");
-            return E_HANDLE;
-        };
-
-        let display = match get_display(state, device_ptr) {
-            Ok(display) => display,
-            Err(err) => return err,
+        // The state lock must not be held across device transfers
+        let display = {
+            let Some(ref state) = *STATE.lock().expect("State is poisoned") else {
+                log::error!("Library function has been called, but the library is not initialized");
+                return E_HANDLE;
+            };
+            match get_display(state, device_ptr) {
+                Ok(display) => display,
+                Err(err) => return err,
+            }
         };
 
         let Ok(page_number) = page_number.try_into() else { return E_INVALIDARG };
@@ -411,14 +477,16 @@ directoutputlib_export! {
 
 directoutputlib_export! {
     fn DirectOutput_DeleteFile(device_ptr: DevicePtr, page_number: DWORD, file_index: DWORD, status: *mut SRequestStatus) -> HRESULT {
-        let Some(ref state) = *STATE.lock().expect("State is poisoned") else {
-            log::error!("Library function has been called, but the library is not initialized");
-            return E_HANDLE;
-        };
-
-        let display = match get_display(state, device_ptr) {
-            Ok(display) => display,
-            Err(err) => return err,
+        // The state lock must not be held across device transfers
+        let display = {
+            let Some(ref state) = *STATE.lock().expect("State is poisoned") else {
+                log::error!("Library function has been called, but the library is not initialized");
+                return E_HANDLE;
+            };
+            match get_display(state, device_ptr) {
+                Ok(display) => display,
+                Err(err) => return err,
+            }
         };
 
         let Ok(page_number) = page_number.try_into() else { return E_INVALIDARG };
@@ -433,17 +501,19 @@ directoutputlib_export! {
 
 directoutputlib_export! {
     fn DirectOutput_GetSerialNumber(device_ptr: DevicePtr, res_serial_number: *mut libc::wchar_t, res_serial_number_size: usize) -> HRESULT {
-        let Some(ref state) = *STATE.lock().expect("State is poisoned") else {
-            log::error!("Library function has been called, but the library is not initialized");
-            return E_HANDLE;
-        };
-
-        let display = match get_display(state, device_ptr) {
-            Ok(display) => display,
-            Err(err) => return err,
+        // The state lock must not be held across device transfers
+        let display = {
+            let Some(ref state) = *STATE.lock().expect("State is poisoned") else {
+                log::error!("Library function has been called, but the library is not initialized");
+                return E_HANDLE;
+            };
+            match get_display(state, device_ptr) {
+                Ok(display) => display,
+                Err(err) => return err,
+            }
         };
 
-        let serial_number = display.serial_number();
+        let Some(serial_number) = display.serial_number() else { return E_HANDLE };
         let serial_number_wide = widestring::WideCString::from_str(serial_number).expect("Could not convert serial number to wide c string");
         if serial_number_wide.len() > res_serial_number_size {
             return E_BUFFERTOOSMALL;
@@ -455,6 +525,304 @@ directoutputlib_export! {
     }
 }
 
+pub const DEVICE_STRING_MANUFACTURER: DWORD = 0;
+pub const DEVICE_STRING_PRODUCT: DWORD = 1;
+pub const DEVICE_STRING_SERIAL_NUMBER: DWORD = 2;
+
+directoutputlib_export! {
+    fn DirectOutputEx_GetDeviceString(device_ptr: DevicePtr, string_kind: DWORD, res_string: *mut libc::wchar_t, res_string_size: DWORD) -> HRESULT {
+        // The state lock must not be held across device transfers
+        let display = {
+            let Some(ref state) = *STATE.lock().expect("State is poisoned") else {
+                log::error!("Library function has been called, but the library is not initialized");
+                return E_HANDLE;
+            };
+            match get_display(state, device_ptr) {
+                Ok(display) => display,
+                Err(err) => return err,
+            }
+        };
+
+        if res_string.is_null() {
+            return E_INVALIDARG;
+        }
+        let Ok(res_string_size): Result<usize, _> = res_string_size.try_into() else { return E_INVALIDARG };
+        let Some(strings) = display.device_strings() else { return E_HANDLE };
+        let string = match string_kind {
+            DEVICE_STRING_MANUFACTURER => strings.manufacturer,
+            DEVICE_STRING_PRODUCT => strings.product,
+            DEVICE_STRING_SERIAL_NUMBER => strings.serial_number,
+            _ => return E_INVALIDARG,
+        };
+        let Some(string) = string else { return E_NOTIMPL };
+        let Ok(string_wide) = widestring::WideCString::from_str(string) else { return E_FAIL };
+        let string_wide = string_wide.as_slice_with_nul();
+        if string_wide.len() > res_string_size {
+            return E_BUFFERTOOSMALL;
+        }
+        unsafe { slice::from_raw_parts_mut(<*mut libc::wchar_t>::cast(res_string), string_wide.len()) }.copy_from_slice(string_wide);
+
+        S_OK
+    }
+}
+
+directoutputlib_export! {
+    fn DirectOutputEx_GetStorageUsage(device_ptr: DevicePtr, res_used: *mut u64, res_remaining: *mut u64) -> HRESULT {
+        // The state lock must not be held across device transfers
+        let display = {
+            let Some(ref state) = *STATE.lock().expect("State is poisoned") else {
+                log::error!("Library function has been called, but the library is not initialized");
+                return E_HANDLE;
+            };
+            match get_display(state, device_ptr) {
+                Ok(display) => display,
+                Err(err) => return err,
+            }
+        };
+
+        let Some(res_used) = (unsafe { res_used.as_mut() }) else { return E_INVALIDARG };
+        let Some(res_remaining) = (unsafe { res_remaining.as_mut() }) else { return E_INVALIDARG };
+        let usage = display.storage_usage();
+        *res_used = usage.used as u64;
+        *res_remaining = usage.remaining_estimate().map_or(u64::MAX, |remaining| remaining as u64);
+
+        S_OK
+    }
+}
+
+#[repr(C)]
+pub struct FrameStats {
+    pub frames: u64,
+    pub failed_frames: u64,
+    pub last_latency_us: u64,
+    pub min_latency_us: u64,
+    pub max_latency_us: u64,
+    pub avg_latency_us: u64,
+}
+
+directoutputlib_export! {
+    fn DirectOutputEx_SetLatencyMode(device_ptr: DevicePtr, enabled: DWORD) -> HRESULT {
+        // The state lock must not be held across device transfers
+        let display = {
+            let Some(ref state) = *STATE.lock().expect("State is poisoned") else {
+                log::error!("Library function has been called, but the library is not initialized");
+                return E_HANDLE;
+            };
+            match get_display(state, device_ptr) {
+                Ok(display) => display,
+                Err(err) => return err,
+            }
+        };
+
+        let enabled = match enabled {
+            0 => false,
+            1 => true,
+            _ => return E_INVALIDARG,
+        };
+        display.set_latency_mode(enabled);
+
+        S_OK
+    }
+}
+
+directoutputlib_export! {
+    fn DirectOutputEx_GetFrameStats(device_ptr: DevicePtr, res_stats: *mut FrameStats) -> HRESULT {
+        // The state lock must not be held across device transfers
+        let display = {
+            let Some(ref state) = *STATE.lock().expect("State is poisoned") else {
+                log::error!("Library function has been called, but the library is not initialized");
+                return E_HANDLE;
+            };
+            match get_display(state, device_ptr) {
+                Ok(display) => display,
+                Err(err) => return err,
+            }
+        };
+
+        let Some(res_stats) = (unsafe { res_stats.as_mut() }) else { return E_INVALIDARG };
+        let stats = display.frame_stats();
+        *res_stats = FrameStats {
+            frames: stats.frames,
+            failed_frames: stats.failed_frames,
+            last_latency_us: stats.last_latency.as_micros() as u64,
+            min_latency_us: stats.min_latency.as_micros() as u64,
+            max_latency_us: stats.max_latency.as_micros() as u64,
+            avg_latency_us: stats.avg_latency().as_micros() as u64,
+        };
+
+        S_OK
+    }
+}
+
+pub const IDLE_ACTION_NONE: DWORD = 0;
+pub const IDLE_ACTION_BLANK: DWORD = 1;
+pub const IDLE_ACTION_CYCLE_FILES: DWORD = 2;
+pub const IDLE_ACTION_CYCLE_IMAGES: DWORD = 3;
+
+directoutputlib_export! {
+    fn DirectOutputEx_SetIdleBehavior(device_ptr: DevicePtr, page_number: DWORD, action: DWORD, timeout_ms: DWORD, interval_ms: DWORD, image_index: DWORD, files_count: DWORD, files: *const DWORD) -> HRESULT {
+        // The state lock must not be held across device transfers
+        let display = {
+            let Some(ref state) = *STATE.lock().expect("State is poisoned") else {
+                log::error!("Library function has been called, but the library is not initialized");
+                return E_HANDLE;
+            };
+            match get_display(state, device_ptr) {
+                Ok(display) => display,
+                Err(err) => return err,
+            }
+        };
+
+        let action = match action {
+            IDLE_ACTION_NONE => {
+                display.set_idle_behavior(None);
+                return S_OK;
+            }
+            IDLE_ACTION_BLANK => devices::IdleAction::Blank,
+            IDLE_ACTION_CYCLE_FILES => {
+                let Ok(index) = image_index.try_into() else { return E_INVALIDARG };
+                let Ok(files_count) = files_count.try_into() else { return E_INVALIDARG };
+                if files.is_null() || files_count == 0 {
+                    return E_INVALIDARG;
+                }
+                let files = unsafe { slice::from_raw_parts(files, files_count) };
+                let Ok(files) = files.iter().map(|file| (*file).try_into()).collect::<Result<Vec<u8>, _>>() else {
+                    return E_INVALIDARG;
+                };
+                devices::IdleAction::CycleFiles { index, files }
+            }
+            IDLE_ACTION_CYCLE_IMAGES => devices::IdleAction::CycleImages,
+            _ => return E_INVALIDARG,
+        };
+        let Ok(page) = page_number.try_into() else { return E_INVALIDARG };
+        let Ok(timeout_ms) = timeout_ms.try_into() else { return E_INVALIDARG };
+        let Ok(interval_ms) = interval_ms.try_into() else { return E_INVALIDARG };
+        if interval_ms == 0 {
+            return E_INVALIDARG;
+        }
+        display.set_idle_behavior(Some(devices::IdleBehavior {
+            page,
+            timeout: Duration::from_millis(timeout_ms),
+            interval: Duration::from_millis(interval_ms),
+            action,
+        }));
+
+        S_OK
+    }
+}
+
+directoutputlib_export! {
+    fn DirectOutputEx_SetGroup(group_name: *const libc::wchar_t, serial_numbers_count: DWORD, serial_numbers: *const *const libc::wchar_t) -> HRESULT {
+        let Some(ref state) = *STATE.lock().expect("State is poisoned") else {
+            log::error!("Library function has been called, but the library is not initialized");
+            return E_HANDLE;
+        };
+
+        let Ok(group_name) = wide_str_arg(group_name) else { return E_INVALIDARG };
+        let Ok(serial_numbers_count) = serial_numbers_count.try_into() else { return E_INVALIDARG };
+        if serial_numbers.is_null() && serial_numbers_count != 0 {
+            return E_INVALIDARG;
+        }
+        let serial_numbers: &[*const libc::wchar_t] = match serial_numbers_count {
+            0 => &[],
+            _ => unsafe { slice::from_raw_parts(serial_numbers, serial_numbers_count) },
+        };
+        let Ok(serial_numbers) = serial_numbers.iter().map(|serial_number| wide_str_arg(*serial_number)).collect::<Result<Vec<_>, _>>() else {
+            return E_INVALIDARG;
+        };
+        state.set_group(group_name, serial_numbers);
+
+        S_OK
+    }
+}
+
+directoutputlib_export! {
+    fn DirectOutputEx_RemoveGroup(group_name: *const libc::wchar_t) -> HRESULT {
+        let Some(ref state) = *STATE.lock().expect("State is poisoned") else {
+            log::error!("Library function has been called, but the library is not initialized");
+            return E_HANDLE;
+        };
+
+        let Ok(group_name) = wide_str_arg(group_name) else { return E_INVALIDARG };
+        match state.remove_group(&group_name) {
+            true => S_OK,
+            false => E_HANDLE,
+        }
+    }
+}
+
+directoutputlib_export! {
+    fn DirectOutputEx_GroupClearImage(group_name: *const libc::wchar_t, page_number: DWORD) -> HRESULT {
+        let Ok(page) = page_number.try_into() else { return E_INVALIDARG };
+        broadcast(group_name, &devices::Broadcast::ClearImage { page })
+    }
+}
+
+directoutputlib_export! {
+    fn DirectOutputEx_GroupSetImage(group_name: *const libc::wchar_t, page_number: DWORD, image_size: DWORD, image: *const u8) -> HRESULT {
+        if image.is_null() {
+            return E_INVALIDARG;
+        }
+        if image_size != 0x38400 {
+            return E_INVALIDARG;
+        }
+        let image_data = unsafe { slice::from_raw_parts(image, 0x38400) };
+        let Ok(page) = page_number.try_into() else { return E_INVALIDARG };
+        broadcast(group_name, &devices::Broadcast::SetImageData { page, data: arrayref::array_ref![image_data, 0, 0x38400] })
+    }
+}
+
+directoutputlib_export! {
+    fn DirectOutputEx_GroupDisplayFile(group_name: *const libc::wchar_t, page_number: DWORD, image_index: DWORD, file_index: DWORD) -> HRESULT {
+        let Ok(page) = page_number.try_into() else { return E_INVALIDARG };
+        let Ok(index) = image_index.try_into() else { return E_INVALIDARG };
+        let Ok(file) = file_index.try_into() else { return E_INVALIDARG };
+        broadcast(group_name, &devices::Broadcast::DisplayFile { page, index, file })
+    }
+}
+
+directoutputlib_export! {
+    fn DirectOutputEx_GroupSetLeds(group_name: *const libc::wchar_t, page_number: DWORD, led_value: DWORD) -> HRESULT {
+        let Ok(page) = page_number.try_into() else { return E_INVALIDARG };
+        let value = match led_value {
+            0 => false,
+            1 => true,
+            _ => return E_INVALIDARG,
+        };
+        broadcast(group_name, &devices::Broadcast::SetLeds { page, value })
+    }
+}
+
+fn broadcast(group_name: *const libc::wchar_t, operation: &devices::Broadcast) -> HRESULT {
+    let Ok(group_name) = wide_str_arg(group_name) else { return E_INVALIDARG };
+    // The state lock must not be held across device transfers
+    let displays = {
+        let Some(ref state) = *STATE.lock().expect("State is poisoned") else {
+            log::error!("Library function has been called, but the library is not initialized");
+            return E_HANDLE;
+        };
+        let Some(displays) = state.group_displays(&group_name) else {
+            log::error!("Library function has been called with a group that doesn't exists");
+            return E_HANDLE;
+        };
+        displays
+    };
+    log::debug!("Broadcasting to group {:?} ({} display(s) connected)", group_name, displays.len());
+    match devices::broadcast(&displays, operation) {
+        0 => S_OK,
+        _ => E_FAIL,
+    }
+}
+
+fn wide_str_arg(ptr: *const libc::wchar_t) -> Result<String, HRESULT> {
+    if ptr.is_null() {
+        return Err(E_INVALIDARG);
+    }
+    unsafe { widestring::WideCStr::from_ptr_str(ptr.cast()) }
+        .to_string()
+        .map_err(|_| E_INVALIDARG)
+}
+
 fn extract_addr(device_ptr: DevicePtr) -> Result<devices::UsbDeviceAddress, HRESULT> {
     if device_ptr as u16 == 0 || device_ptr >= u16::MAX.into() {
         return Err(E_HANDLE);
@@ -0,0 +1,123 @@
+"""
+Example of using the native `fip_*` API (libfip/fip.h) from Python via ctypes,
+doubling as a local test of it. Build the library first (`cargo build`).
+
+    python3 test-ctypes.py [path/to/liblibfip.so] [serial number]
+
+Checks that don't need a device always run (also without USB access);
+the rest are skipped if no device is connected.
+"""
+import ctypes
+import sys
+import time
+
+FIP_API_VERSION = 1
+
+FIP_OK = 0
+FIP_E_INVALID_ARG = -1
+
+FIP_BUTTON_S1 = 0x0100
+FIP_FRAME_SIZE = 320 * 240 * 3
+
+
+class FipDeviceInfo(ctypes.Structure):
+    _fields_ = [
+        ('serial_number', ctypes.c_char * 64),
+        ('manufacturer', ctypes.c_char * 64),
+        ('product', ctypes.c_char * 64),
+        ('bus_number', ctypes.c_uint8),
+        ('address', ctypes.c_uint8),
+    ]
+
+
+def load(path):
+    lib = ctypes.CDLL(path)
+
+    lib.fip_api_version.argtypes = []
+    lib.fip_api_version.restype = ctypes.c_uint32
+    lib.fip_open.argtypes = [ctypes.c_char_p, ctypes.c_uint32]
+    lib.fip_open.restype = ctypes.c_void_p
+    lib.fip_set_frame.argtypes = [ctypes.c_void_p, ctypes.c_uint8, ctypes.c_char_p, ctypes.c_size_t]
+    lib.fip_set_frame.restype = ctypes.c_int32
+    lib.fip_clear_frame.argtypes = [ctypes.c_void_p, ctypes.c_uint8]
+    lib.fip_clear_frame.restype = ctypes.c_int32
+    lib.fip_set_led.argtypes = [ctypes.c_void_p, ctypes.c_uint8, ctypes.c_uint8, ctypes.c_uint8]
+    lib.fip_set_led.restype = ctypes.c_int32
+    lib.fip_get_info.argtypes = [ctypes.c_void_p, ctypes.POINTER(FipDeviceInfo)]
+    lib.fip_get_info.restype = ctypes.c_int32
+    lib.fip_get_buttons.argtypes = [ctypes.c_void_p, ctypes.POINTER(ctypes.c_uint32)]
+    lib.fip_get_buttons.restype = ctypes.c_int32
+    lib.fip_poll_buttons.argtypes = [ctypes.c_void_p, ctypes.POINTER(ctypes.c_uint32)]
+    lib.fip_poll_buttons.restype = ctypes.c_int32
+    lib.fip_close.argtypes = [ctypes.c_void_p]
+    lib.fip_close.restype = None
+
+    return lib
+
+
+def test_without_device(lib):
+    assert lib.fip_api_version() == FIP_API_VERSION, lib.fip_api_version()
+
+    # NULL device handles must be rejected, not crash
+    buttons = ctypes.c_uint32()
+    assert lib.fip_set_frame(None, 0, b'\0' * FIP_FRAME_SIZE, FIP_FRAME_SIZE) == FIP_E_INVALID_ARG
+    assert lib.fip_get_buttons(None, ctypes.byref(buttons)) == FIP_E_INVALID_ARG
+    assert lib.fip_poll_buttons(None, ctypes.byref(buttons)) == FIP_E_INVALID_ARG
+    lib.fip_close(None)
+
+    # a device that's not there (or no USB access at all) is reported with NULL
+    assert not lib.fip_open(b'no such serial number', 100)
+
+
+def test_with_device(lib, device):
+    info = FipDeviceInfo()
+    assert lib.fip_get_info(device, ctypes.byref(info)) == FIP_OK
+    print('device:', info.serial_number, info.manufacturer, info.product, info.bus_number, info.address)
+    assert info.serial_number
+
+    # the frame size is checked before anything is sent to the device
+    assert lib.fip_set_frame(device, 0, b'\0' * 16, 16) == FIP_E_INVALID_ARG
+
+    # red, green, blue stripes
+    stripe = FIP_FRAME_SIZE // 3
+    frame = b'\xff\0\0' * (stripe // 3) + b'\0\xff\0' * (stripe // 3) + b'\0\0\xff' * (stripe // 3)
+    assert lib.fip_set_frame(device, 0, frame, len(frame)) == FIP_OK
+
+    assert lib.fip_set_led(device, 0, 0, 1) == FIP_E_INVALID_ARG
+    for value in (1, 0):
+        for index in range(1, 9):
+            assert lib.fip_set_led(device, 0, index, value) == FIP_OK
+            time.sleep(0.05)
+
+    print('press S1 within 10 seconds (or wait to skip)...')
+    buttons = ctypes.c_uint32()
+    deadline = time.monotonic() + 10
+    while time.monotonic() < deadline:
+        if lib.fip_poll_buttons(device, ctypes.byref(buttons)) == 1:
+            print('buttons:', hex(buttons.value))
+            if buttons.value & FIP_BUTTON_S1:
+                assert lib.fip_get_buttons(device, ctypes.byref(buttons)) == FIP_OK
+                break
+        time.sleep(0.01)
+
+    assert lib.fip_clear_frame(device, 0) == FIP_OK
+
+
+def main():
+    lib = load(sys.argv[1] if len(sys.argv) > 1 else './target/debug/liblibfip.so')
+    test_without_device(lib)
+
+    serial_number = sys.argv[2].encode() if len(sys.argv) > 2 else None
+    device = lib.fip_open(serial_number, 2000)
+    if not device:
+        print('No devices found, skipping device tests')
+        return
+    try:
+        test_with_device(lib, device)
+    finally:
+        lib.fip_close(device)
+    print('OK')
+
+
+if __name__ == '__main__':
+    main()